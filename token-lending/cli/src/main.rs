@@ -0,0 +1,1356 @@
+use clap::{
+    crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings, Arg,
+    SubCommand,
+};
+use solana_clap_utils::{
+    input_parsers::pubkey_of,
+    input_validators::{is_amount, is_keypair, is_parsable, is_pubkey, is_url},
+    keypair::signer_from_path,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+    transaction::Transaction,
+};
+use spl_token::{
+    state::{Account as Token, Mint},
+    ui_amount_to_amount,
+};
+use spl_token_lending::{
+    dex_market::{DexMarket, ASKS_OFFSET, BIDS_OFFSET},
+    instruction::{
+        accrue_reserve_interest, borrow_reserve_liquidity, deposit_reserve_liquidity,
+        init_lending_market, init_obligation, init_reserve, liquidate_obligation,
+        refresh_obligation, repay_reserve_liquidity, BorrowAmountType, PriceAccounts,
+    },
+    math::{Decimal, Rate, TryDiv, TryMul},
+    state::{LendingMarket, Obligation, Reserve, ReserveConfig, ReserveFees},
+};
+use std::process::exit;
+
+struct Config {
+    rpc_client: RpcClient,
+    verbose: bool,
+    owner: Box<dyn Signer>,
+    fee_payer: Box<dyn Signer>,
+    dry_run: bool,
+}
+
+type Error = Box<dyn std::error::Error>;
+type CommandResult = Result<(), Error>;
+
+/// Size of the scratch account the program uses to load dex market order books
+const MEMORY_LEN: u64 = 65548;
+
+macro_rules! unique_signers {
+    ($vec:ident) => {
+        $vec.sort_by_key(|l| l.pubkey());
+        $vec.dedup();
+    };
+}
+
+fn send_transaction(
+    config: &Config,
+    instructions: &[Instruction],
+    mut signers: Vec<&dyn Signer>,
+) -> CommandResult {
+    let mut transaction =
+        Transaction::new_with_payer(instructions, Some(&config.fee_payer.pubkey()));
+    let (recent_blockhash, _fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    signers.push(config.fee_payer.as_ref());
+    unique_signers!(signers);
+    transaction.try_sign(&signers, recent_blockhash)?;
+
+    if config.dry_run {
+        let result = config.rpc_client.simulate_transaction(&transaction)?;
+        println!("Simulate result: {:?}", result);
+    } else {
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)?;
+        println!("Signature: {}", signature);
+    }
+    Ok(())
+}
+
+fn get_reserve(config: &Config, reserve_pubkey: &Pubkey) -> Result<Reserve, Error> {
+    let data = config.rpc_client.get_account_data(reserve_pubkey)?;
+    Ok(Reserve::unpack(&data)?)
+}
+
+fn get_obligation(config: &Config, obligation_pubkey: &Pubkey) -> Result<Obligation, Error> {
+    let data = config.rpc_client.get_account_data(obligation_pubkey)?;
+    Ok(Obligation::unpack(&data)?)
+}
+
+fn lending_market_authority(program_id: &Pubkey, lending_market_pubkey: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], program_id).0
+}
+
+/// Find the dex market trading between two reserves and the order book side to search, which
+/// is the asks when the source reserve holds the quote currency and the bids otherwise
+fn get_dex_market_orders(
+    config: &Config,
+    source_reserve: &Reserve,
+    other_reserve: &Reserve,
+) -> Result<(Pubkey, Pubkey), Error> {
+    let (dex_market_pubkey, orders_offset) =
+        match (source_reserve.dex_market, other_reserve.dex_market) {
+            (COption::Some(dex_market_pubkey), _) => (dex_market_pubkey, BIDS_OFFSET),
+            (_, COption::Some(dex_market_pubkey)) => (dex_market_pubkey, ASKS_OFFSET),
+            _ => return Err("Neither reserve has a dex market".into()),
+        };
+    let data = config.rpc_client.get_account_data(&dex_market_pubkey)?;
+    let orders_pubkey = DexMarket::pubkey_at_offset(&data, orders_offset);
+    Ok((dex_market_pubkey, orders_pubkey))
+}
+
+/// Find the accounts used to value an obligation's collateral and loan, which are the reserve
+/// oracles when both reserves have one and the dex market when neither does
+fn get_price_accounts(
+    config: &Config,
+    collateral_reserve: &Reserve,
+    borrow_reserve: &Reserve,
+    memory: &Keypair,
+) -> Result<PriceAccounts, Error> {
+    match (collateral_reserve.oracle, borrow_reserve.oracle) {
+        (
+            COption::Some(collateral_reserve_oracle_pubkey),
+            COption::Some(borrow_reserve_oracle_pubkey),
+        ) => {
+            return Ok(PriceAccounts::Oracles {
+                collateral_reserve_oracle_pubkey,
+                borrow_reserve_oracle_pubkey,
+            })
+        }
+        (COption::None, COption::None) => {}
+        _ => return Err("Only one reserve has a price oracle".into()),
+    }
+
+    let (dex_market_pubkey, dex_market_order_book_side_pubkey) =
+        get_dex_market_orders(config, collateral_reserve, borrow_reserve)?;
+    Ok(PriceAccounts::DexMarket {
+        dex_market_pubkey,
+        dex_market_order_book_side_pubkey,
+        memory_pubkey: memory.pubkey(),
+    })
+}
+
+fn create_memory_account(config: &Config, program_id: &Pubkey, memory: &Keypair) -> Instruction {
+    create_account(
+        &config.fee_payer.pubkey(),
+        &memory.pubkey(),
+        0,
+        MEMORY_LEN,
+        program_id,
+    )
+}
+
+/// Strip trailing zeros from a formatted wad value
+fn trim_wad(value: String) -> String {
+    value
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Format a wad amount of base units as a whole token amount
+fn format_token_amount(wads: Decimal, decimals: u8) -> Result<String, Error> {
+    let amount = wads.try_div(10u64.pow(decimals as u32))?;
+    Ok(trim_wad(amount.to_string()))
+}
+
+/// Format a reserve limit, where zero means no limit
+fn format_limit(limit: u64, decimals: u8) -> Result<String, Error> {
+    if limit == 0 {
+        Ok("none".to_string())
+    } else {
+        format_token_amount(Decimal::from(limit), decimals)
+    }
+}
+
+/// Format a rate as a percentage
+fn format_rate(rate: Rate) -> Result<String, Error> {
+    let percent = Decimal::from(rate).try_mul(100u64)?;
+    Ok(format!("{}%", trim_wad(percent.to_string())))
+}
+
+fn format_optional_pubkey(pubkey: COption<Pubkey>) -> String {
+    match pubkey {
+        COption::Some(pubkey) => pubkey.to_string(),
+        COption::None => "none".to_string(),
+    }
+}
+
+fn command_create_market(
+    config: &Config,
+    program_id: &Pubkey,
+    quote_token_mint: &Pubkey,
+) -> CommandResult {
+    let lending_market = Keypair::new();
+    println!("Creating lending market {}", lending_market.pubkey());
+
+    let lending_market_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(LendingMarket::LEN)?;
+
+    send_transaction(
+        config,
+        &[
+            create_account(
+                &config.fee_payer.pubkey(),
+                &lending_market.pubkey(),
+                lending_market_balance,
+                LendingMarket::LEN as u64,
+                program_id,
+            ),
+            init_lending_market(
+                *program_id,
+                lending_market.pubkey(),
+                config.owner.pubkey(),
+                *quote_token_mint,
+            ),
+        ],
+        vec![&lending_market],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_add_reserve(
+    config: &Config,
+    program_id: &Pubkey,
+    lending_market_pubkey: &Pubkey,
+    liquidity_source_pubkey: &Pubkey,
+    ui_amount: f64,
+    reserve_config: ReserveConfig,
+    dex_market_pubkey: Option<Pubkey>,
+    oracle_pubkey: Option<Pubkey>,
+) -> CommandResult {
+    let liquidity_source_data = config
+        .rpc_client
+        .get_account_data(liquidity_source_pubkey)?;
+    let liquidity_source = Token::unpack(&liquidity_source_data)?;
+    let liquidity_mint_data = config.rpc_client.get_account_data(&liquidity_source.mint)?;
+    let liquidity_mint = Mint::unpack(&liquidity_mint_data)?;
+    let liquidity_amount = ui_amount_to_amount(ui_amount, liquidity_mint.decimals);
+
+    let reserve = Keypair::new();
+    let collateral_mint = Keypair::new();
+    let collateral_supply = Keypair::new();
+    let collateral_fees_receiver = Keypair::new();
+    let liquidity_supply = Keypair::new();
+    let liquidity_fees_receiver = Keypair::new();
+    let user_collateral = Keypair::new();
+    println!("Creating reserve {}", reserve.pubkey());
+    println!(
+        "Creating collateral token account {}",
+        user_collateral.pubkey()
+    );
+
+    let mint_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Mint::LEN)?;
+    let token_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Token::LEN)?;
+    let reserve_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Reserve::LEN)?;
+
+    let mut instructions = vec![create_account(
+        &config.fee_payer.pubkey(),
+        &collateral_mint.pubkey(),
+        mint_balance,
+        Mint::LEN as u64,
+        &spl_token::id(),
+    )];
+    for token_account in &[
+        &collateral_supply,
+        &collateral_fees_receiver,
+        &liquidity_supply,
+        &liquidity_fees_receiver,
+        &user_collateral,
+    ] {
+        instructions.push(create_account(
+            &config.fee_payer.pubkey(),
+            &token_account.pubkey(),
+            token_balance,
+            Token::LEN as u64,
+            &spl_token::id(),
+        ));
+    }
+    instructions.push(create_account(
+        &config.fee_payer.pubkey(),
+        &reserve.pubkey(),
+        reserve_balance,
+        Reserve::LEN as u64,
+        program_id,
+    ));
+
+    // too many new accounts to fit in a single transaction with the init instruction
+    send_transaction(
+        config,
+        &instructions,
+        vec![
+            &reserve,
+            &collateral_mint,
+            &collateral_supply,
+            &collateral_fees_receiver,
+            &liquidity_supply,
+            &liquidity_fees_receiver,
+            &user_collateral,
+        ],
+    )?;
+
+    send_transaction(
+        config,
+        &[init_reserve(
+            *program_id,
+            liquidity_amount,
+            reserve_config,
+            *liquidity_source_pubkey,
+            user_collateral.pubkey(),
+            reserve.pubkey(),
+            liquidity_source.mint,
+            liquidity_supply.pubkey(),
+            collateral_mint.pubkey(),
+            collateral_supply.pubkey(),
+            collateral_fees_receiver.pubkey(),
+            liquidity_fees_receiver.pubkey(),
+            *lending_market_pubkey,
+            config.owner.pubkey(),
+            config.owner.pubkey(),
+            dex_market_pubkey,
+            oracle_pubkey,
+        )],
+        vec![config.owner.as_ref()],
+    )
+}
+
+fn command_deposit(
+    config: &Config,
+    program_id: &Pubkey,
+    reserve_pubkey: &Pubkey,
+    source_liquidity_pubkey: &Pubkey,
+    destination_collateral_pubkey: &Pubkey,
+    ui_amount: f64,
+) -> CommandResult {
+    let reserve = get_reserve(config, reserve_pubkey)?;
+    let liquidity_amount = ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals);
+
+    send_transaction(
+        config,
+        &[
+            accrue_reserve_interest(*program_id, vec![*reserve_pubkey]),
+            deposit_reserve_liquidity(
+                *program_id,
+                liquidity_amount,
+                *source_liquidity_pubkey,
+                *destination_collateral_pubkey,
+                *reserve_pubkey,
+                reserve.liquidity.supply_pubkey,
+                reserve.collateral.mint_pubkey,
+                reserve.lending_market,
+                lending_market_authority(program_id, &reserve.lending_market),
+                config.owner.pubkey(),
+            ),
+        ],
+        vec![config.owner.as_ref()],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_borrow(
+    config: &Config,
+    program_id: &Pubkey,
+    deposit_reserve_pubkey: &Pubkey,
+    borrow_reserve_pubkey: &Pubkey,
+    source_collateral_pubkey: &Pubkey,
+    destination_liquidity_pubkey: &Pubkey,
+    ui_amount: f64,
+    amount_type: BorrowAmountType,
+    obligation_accounts: Option<(Pubkey, Pubkey)>,
+) -> CommandResult {
+    let deposit_reserve = get_reserve(config, deposit_reserve_pubkey)?;
+    let borrow_reserve = get_reserve(config, borrow_reserve_pubkey)?;
+    let lending_market_pubkey = borrow_reserve.lending_market;
+    let amount = match amount_type {
+        BorrowAmountType::LiquidityBorrowAmount => {
+            ui_amount_to_amount(ui_amount, borrow_reserve.liquidity.mint_decimals)
+        }
+        BorrowAmountType::CollateralDepositAmount => {
+            ui_amount_to_amount(ui_amount, deposit_reserve.liquidity.mint_decimals)
+        }
+    };
+
+    let (obligation_pubkey, obligation_token_output_pubkey) = match obligation_accounts {
+        Some(obligation_accounts) => obligation_accounts,
+        None => {
+            let obligation = Keypair::new();
+            let obligation_token_mint = Keypair::new();
+            let obligation_token_output = Keypair::new();
+            println!("Creating obligation {}", obligation.pubkey());
+            println!(
+                "Creating obligation token account {}",
+                obligation_token_output.pubkey()
+            );
+
+            let obligation_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(Obligation::LEN)?;
+            let mint_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(Mint::LEN)?;
+            let token_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(Token::LEN)?;
+
+            send_transaction(
+                config,
+                &[
+                    create_account(
+                        &config.fee_payer.pubkey(),
+                        &obligation.pubkey(),
+                        obligation_balance,
+                        Obligation::LEN as u64,
+                        program_id,
+                    ),
+                    create_account(
+                        &config.fee_payer.pubkey(),
+                        &obligation_token_mint.pubkey(),
+                        mint_balance,
+                        Mint::LEN as u64,
+                        &spl_token::id(),
+                    ),
+                    create_account(
+                        &config.fee_payer.pubkey(),
+                        &obligation_token_output.pubkey(),
+                        token_balance,
+                        Token::LEN as u64,
+                        &spl_token::id(),
+                    ),
+                    accrue_reserve_interest(*program_id, vec![*borrow_reserve_pubkey]),
+                    init_obligation(
+                        *program_id,
+                        *deposit_reserve_pubkey,
+                        *borrow_reserve_pubkey,
+                        lending_market_pubkey,
+                        obligation.pubkey(),
+                        obligation_token_mint.pubkey(),
+                        obligation_token_output.pubkey(),
+                        config.owner.pubkey(),
+                    ),
+                ],
+                vec![
+                    &obligation,
+                    &obligation_token_mint,
+                    &obligation_token_output,
+                ],
+            )?;
+
+            (obligation.pubkey(), obligation_token_output.pubkey())
+        }
+    };
+    let obligation = get_obligation(config, &obligation_pubkey)?;
+
+    let memory = Keypair::new();
+    let price_accounts = get_price_accounts(config, &deposit_reserve, &borrow_reserve, &memory)?;
+
+    send_transaction(
+        config,
+        &[
+            create_memory_account(config, program_id, &memory),
+            accrue_reserve_interest(
+                *program_id,
+                vec![*deposit_reserve_pubkey, *borrow_reserve_pubkey],
+            ),
+            refresh_obligation(
+                *program_id,
+                obligation_pubkey,
+                *deposit_reserve_pubkey,
+                *borrow_reserve_pubkey,
+                lending_market_pubkey,
+                price_accounts,
+            ),
+            borrow_reserve_liquidity(
+                *program_id,
+                amount,
+                amount_type,
+                *source_collateral_pubkey,
+                *destination_liquidity_pubkey,
+                *deposit_reserve_pubkey,
+                deposit_reserve.collateral.supply_pubkey,
+                deposit_reserve.collateral.fees_receiver,
+                *borrow_reserve_pubkey,
+                borrow_reserve.liquidity.supply_pubkey,
+                lending_market_pubkey,
+                lending_market_authority(program_id, &lending_market_pubkey),
+                config.owner.pubkey(),
+                obligation_pubkey,
+                obligation.token_mint,
+                obligation_token_output_pubkey,
+                price_accounts,
+                None,
+            ),
+        ],
+        vec![config.owner.as_ref(), &memory],
+    )
+}
+
+fn command_repay(
+    config: &Config,
+    program_id: &Pubkey,
+    obligation_pubkey: &Pubkey,
+    obligation_token_input_pubkey: &Pubkey,
+    source_liquidity_pubkey: &Pubkey,
+    destination_collateral_pubkey: &Pubkey,
+    ui_amount: Option<f64>,
+) -> CommandResult {
+    let obligation = get_obligation(config, obligation_pubkey)?;
+    let repay_reserve = get_reserve(config, &obligation.borrow_reserve)?;
+    let withdraw_reserve = get_reserve(config, &obligation.collateral_reserve)?;
+    let lending_market_pubkey = repay_reserve.lending_market;
+    // the program caps the repay amount at the outstanding borrow
+    let liquidity_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        ui_amount_to_amount(ui_amount, repay_reserve.liquidity.mint_decimals)
+    });
+
+    send_transaction(
+        config,
+        &[
+            accrue_reserve_interest(
+                *program_id,
+                vec![obligation.borrow_reserve, obligation.collateral_reserve],
+            ),
+            repay_reserve_liquidity(
+                *program_id,
+                liquidity_amount,
+                *source_liquidity_pubkey,
+                *destination_collateral_pubkey,
+                obligation.borrow_reserve,
+                repay_reserve.liquidity.supply_pubkey,
+                obligation.collateral_reserve,
+                withdraw_reserve.collateral.supply_pubkey,
+                *obligation_pubkey,
+                obligation.token_mint,
+                *obligation_token_input_pubkey,
+                lending_market_pubkey,
+                lending_market_authority(program_id, &lending_market_pubkey),
+                config.owner.pubkey(),
+            ),
+        ],
+        vec![config.owner.as_ref()],
+    )
+}
+
+fn command_liquidate(
+    config: &Config,
+    program_id: &Pubkey,
+    obligation_pubkey: &Pubkey,
+    source_liquidity_pubkey: &Pubkey,
+    destination_collateral_pubkey: &Pubkey,
+    ui_amount: f64,
+) -> CommandResult {
+    let obligation = get_obligation(config, obligation_pubkey)?;
+    let repay_reserve = get_reserve(config, &obligation.borrow_reserve)?;
+    let withdraw_reserve = get_reserve(config, &obligation.collateral_reserve)?;
+    let lending_market_pubkey = repay_reserve.lending_market;
+    let liquidity_amount = ui_amount_to_amount(ui_amount, repay_reserve.liquidity.mint_decimals);
+
+    let memory = Keypair::new();
+    let price_accounts = get_price_accounts(config, &withdraw_reserve, &repay_reserve, &memory)?;
+
+    send_transaction(
+        config,
+        &[
+            create_memory_account(config, program_id, &memory),
+            accrue_reserve_interest(
+                *program_id,
+                vec![obligation.borrow_reserve, obligation.collateral_reserve],
+            ),
+            refresh_obligation(
+                *program_id,
+                *obligation_pubkey,
+                obligation.collateral_reserve,
+                obligation.borrow_reserve,
+                lending_market_pubkey,
+                price_accounts,
+            ),
+            liquidate_obligation(
+                *program_id,
+                liquidity_amount,
+                *source_liquidity_pubkey,
+                *destination_collateral_pubkey,
+                obligation.borrow_reserve,
+                repay_reserve.liquidity.supply_pubkey,
+                obligation.collateral_reserve,
+                withdraw_reserve.collateral.supply_pubkey,
+                *obligation_pubkey,
+                lending_market_pubkey,
+                lending_market_authority(program_id, &lending_market_pubkey),
+                config.owner.pubkey(),
+            ),
+        ],
+        vec![config.owner.as_ref(), &memory],
+    )
+}
+
+fn command_show_reserve(config: &Config, reserve_pubkey: &Pubkey) -> CommandResult {
+    let reserve = get_reserve(config, reserve_pubkey)?;
+    let decimals = reserve.liquidity.mint_decimals;
+
+    println!("Reserve: {}", reserve_pubkey);
+    println!("Lending market: {}", reserve.lending_market);
+    println!("Last update slot: {}", reserve.last_update_slot);
+    println!("Liquidity mint: {}", reserve.liquidity.mint_pubkey);
+    println!("Collateral mint: {}", reserve.collateral.mint_pubkey);
+    println!("Dex market: {}", format_optional_pubkey(reserve.dex_market));
+    println!("Oracle: {}", format_optional_pubkey(reserve.oracle));
+    if config.verbose {
+        println!("Liquidity supply: {}", reserve.liquidity.supply_pubkey);
+        println!(
+            "Liquidity fees receiver: {}",
+            reserve.liquidity.fees_receiver
+        );
+        println!("Collateral supply: {}", reserve.collateral.supply_pubkey);
+        println!(
+            "Collateral fees receiver: {}",
+            reserve.collateral.fees_receiver
+        );
+    }
+
+    println!(
+        "Total supply: {}",
+        format_token_amount(reserve.liquidity.total_supply()?, decimals)?
+    );
+    println!(
+        "Available liquidity: {}",
+        format_token_amount(Decimal::from(reserve.liquidity.available_amount), decimals)?
+    );
+    println!(
+        "Borrowed liquidity: {}",
+        format_token_amount(reserve.liquidity.borrowed_amount_wads, decimals)?
+    );
+    println!(
+        "Accumulated protocol fees: {}",
+        format_token_amount(reserve.liquidity.accumulated_protocol_fees_wads, decimals)?
+    );
+    println!(
+        "Collateral supply: {}",
+        format_token_amount(
+            Decimal::from(reserve.collateral.mint_total_supply),
+            decimals
+        )?
+    );
+    println!(
+        "Cumulative borrow rate: {}",
+        trim_wad(reserve.cumulative_borrow_rate_wads.to_string())
+    );
+    println!(
+        "Utilization rate: {}",
+        format_rate(reserve.liquidity.utilization_rate()?)?
+    );
+    println!(
+        "Current borrow rate: {}",
+        format_rate(reserve.current_borrow_rate()?)?
+    );
+
+    let reserve_config = reserve.config;
+    println!("Config:");
+    println!(
+        "  Optimal utilization rate: {}%",
+        reserve_config.optimal_utilization_rate
+    );
+    println!(
+        "  Loan to value ratio: {}%",
+        reserve_config.loan_to_value_ratio
+    );
+    println!(
+        "  Liquidation threshold: {}%",
+        reserve_config.liquidation_threshold
+    );
+    println!("  Liquidation bonus: {}%", reserve_config.liquidation_bonus);
+    println!(
+        "  Liquidation close factor: {}% - {}%",
+        reserve_config.min_liquidation_close_factor, reserve_config.max_liquidation_close_factor
+    );
+    println!("  Base borrow rate: {}%", reserve_config.base_borrow_rate);
+    println!(
+        "  Borrow rate slopes: {}%, {}%",
+        reserve_config.borrow_rate_slope_1, reserve_config.borrow_rate_slope_2
+    );
+    println!(
+        "  Deposit limit: {}",
+        format_limit(reserve_config.deposit_limit, decimals)?
+    );
+    println!(
+        "  Borrow limit: {}",
+        format_limit(reserve_config.borrow_limit, decimals)?
+    );
+    println!(
+        "  Borrow fee: {}",
+        format_rate(Rate::from_scaled_val(reserve_config.fees.borrow_fee_wad))?
+    );
+    println!(
+        "  Host fee percentage: {}%",
+        reserve_config.fees.host_fee_percentage
+    );
+    println!(
+        "  Protocol take rate: {}%",
+        reserve_config.fees.protocol_take_rate
+    );
+
+    Ok(())
+}
+
+fn main() {
+    let default_program_id = spl_token_lending::id().to_string();
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(&config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transaction instead of executing"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program")
+                .value_name("PROGRAM_ID")
+                .validator(is_pubkey)
+                .takes_value(true)
+                .global(true)
+                .default_value(&default_program_id)
+                .help("Token lending program id"),
+        )
+        .arg(
+            Arg::with_name("owner")
+                .long("owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the lending market owner or token account owner. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fee_payer")
+                .long("fee-payer")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the fee-payer account. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .subcommand(SubCommand::with_name("create-market").about("Create a new lending market")
+            .arg(
+                Arg::with_name("quote_mint")
+                    .long("quote-mint")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Mint of the quote currency that reserve values are measured in."),
+            )
+        )
+        .subcommand(SubCommand::with_name("add-reserve").about("Add a reserve to a lending market. Must be signed by the market owner.")
+            .arg(
+                Arg::with_name("market")
+                    .long("market")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Lending market address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account of the reserve liquidity to make the initial deposit from."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity tokens to deposit."),
+            )
+            .arg(
+                Arg::with_name("dex_market")
+                    .long("dex-market")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Dex market trading the reserve liquidity against the quote currency. Omit for the quote currency reserve."),
+            )
+            .arg(
+                Arg::with_name("oracle")
+                    .long("oracle")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Price oracle for the reserve liquidity."),
+            )
+            .arg(
+                Arg::with_name("optimal_utilization_rate")
+                    .long("optimal-utilization-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("80")
+                    .help("Utilization rate at which the second borrow rate slope takes effect."),
+            )
+            .arg(
+                Arg::with_name("loan_to_value_ratio")
+                    .long("loan-to-value-ratio")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("75")
+                    .help("Percentage of collateral value that can be borrowed."),
+            )
+            .arg(
+                Arg::with_name("liquidation_bonus")
+                    .long("liquidation-bonus")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("5")
+                    .help("Bonus a liquidator gets when repaying part of an unhealthy obligation."),
+            )
+            .arg(
+                Arg::with_name("liquidation_threshold")
+                    .long("liquidation-threshold")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("80")
+                    .help("Loan to value ratio at which an obligation can be liquidated."),
+            )
+            .arg(
+                Arg::with_name("min_liquidation_close_factor")
+                    .long("min-liquidation-close-factor")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("50")
+                    .help("Percentage of a borrow that can be liquidated at the liquidation threshold."),
+            )
+            .arg(
+                Arg::with_name("max_liquidation_close_factor")
+                    .long("max-liquidation-close-factor")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("100")
+                    .help("Percentage of a borrow that can be liquidated at 100% loan to value."),
+            )
+            .arg(
+                Arg::with_name("base_borrow_rate")
+                    .long("base-borrow-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Borrow rate at zero utilization."),
+            )
+            .arg(
+                Arg::with_name("borrow_rate_slope_1")
+                    .long("borrow-rate-slope-1")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("4")
+                    .help("Borrow rate increase from zero to optimal utilization."),
+            )
+            .arg(
+                Arg::with_name("borrow_rate_slope_2")
+                    .long("borrow-rate-slope-2")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("26")
+                    .help("Borrow rate increase from optimal to full utilization."),
+            )
+            .arg(
+                Arg::with_name("deposit_limit")
+                    .long("deposit-limit")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Maximum total supply in base units. Defaults to 0, meaning no limit."),
+            )
+            .arg(
+                Arg::with_name("borrow_limit")
+                    .long("borrow-limit")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Maximum total borrows in base units. Defaults to 0, meaning no limit."),
+            )
+            .arg(
+                Arg::with_name("borrow_fee_wad")
+                    .long("borrow-fee-wad")
+                    .validator(is_parsable::<u64>)
+                    .value_name("WAD")
+                    .takes_value(true)
+                    .default_value("100000000000000")
+                    .help("Borrow fee as a wad, where 1e18 is 100%."),
+            )
+            .arg(
+                Arg::with_name("host_fee_percentage")
+                    .long("host-fee-percentage")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("20")
+                    .help("Percentage of the borrow fee paid to the host."),
+            )
+            .arg(
+                Arg::with_name("protocol_take_rate")
+                    .long("protocol-take-rate")
+                    .validator(is_parsable::<u8>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("10")
+                    .help("Percentage of borrow interest kept as protocol fees."),
+            )
+        )
+        .subcommand(SubCommand::with_name("deposit").about("Deposit liquidity into a reserve in exchange for collateral")
+            .arg(
+                Arg::with_name("reserve")
+                    .long("reserve")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve address."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to deposit liquidity from."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to receive reserve collateral."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity tokens to deposit."),
+            )
+        )
+        .subcommand(SubCommand::with_name("borrow").about("Borrow liquidity from a reserve against deposited collateral")
+            .arg(
+                Arg::with_name("deposit_reserve")
+                    .long("deposit-reserve")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve the collateral belongs to."),
+            )
+            .arg(
+                Arg::with_name("borrow_reserve")
+                    .long("borrow-reserve")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reserve to borrow liquidity from."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to deposit collateral from."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to receive borrowed liquidity."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity tokens to borrow."),
+            )
+            .arg(
+                Arg::with_name("collateral_amount")
+                    .long("collateral-amount")
+                    .takes_value(false)
+                    .help("Treat the amount as collateral tokens to deposit instead of liquidity to borrow."),
+            )
+            .arg(
+                Arg::with_name("obligation")
+                    .long("obligation")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .requires("obligation_token_account")
+                    .help("Existing obligation to borrow against. Defaults to creating a new obligation."),
+            )
+            .arg(
+                Arg::with_name("obligation_token_account")
+                    .long("obligation-token-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .requires("obligation")
+                    .help("Token account to receive obligation tokens for an existing obligation."),
+            )
+        )
+        .subcommand(SubCommand::with_name("repay").about("Repay borrowed liquidity and withdraw collateral")
+            .arg(
+                Arg::with_name("obligation")
+                    .long("obligation")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation to repay."),
+            )
+            .arg(
+                Arg::with_name("obligation_token_account")
+                    .long("obligation-token-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account holding the obligation tokens to burn."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to repay liquidity from."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to receive withdrawn collateral."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(|value| if value == "ALL" { Ok(()) } else { is_amount(value) })
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity tokens to repay, or ALL to repay the whole borrow."),
+            )
+        )
+        .subcommand(SubCommand::with_name("liquidate").about("Repay part of an unhealthy obligation in exchange for its collateral")
+            .arg(
+                Arg::with_name("obligation")
+                    .long("obligation")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Obligation to liquidate."),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to repay liquidity from."),
+            )
+            .arg(
+                Arg::with_name("destination")
+                    .long("destination")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Token account to receive liquidated collateral."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .long("amount")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of liquidity tokens to repay."),
+            )
+        )
+        .subcommand(SubCommand::with_name("show-reserve").about("Display the state of a reserve")
+            .arg(
+                Arg::with_name("reserve")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .index(1)
+                    .required(true)
+                    .help("Reserve address."),
+            )
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+
+        let owner = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let fee_payer = signer_from_path(
+            &matches,
+            &cli_config.keypair_path,
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let verbose = matches.is_present("verbose");
+        let dry_run = matches.is_present("dry_run");
+
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            verbose,
+            owner,
+            fee_payer,
+            dry_run,
+        }
+    };
+
+    solana_logger::setup_with_default("solana=info");
+
+    let program_id = pubkey_of(&matches, "program_id").unwrap();
+
+    let _ = match matches.subcommand() {
+        ("create-market", Some(arg_matches)) => {
+            let quote_mint = pubkey_of(arg_matches, "quote_mint").unwrap();
+            command_create_market(&config, &program_id, &quote_mint)
+        }
+        ("add-reserve", Some(arg_matches)) => {
+            let lending_market = pubkey_of(arg_matches, "market").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let dex_market = pubkey_of(arg_matches, "dex_market");
+            let oracle = pubkey_of(arg_matches, "oracle");
+            let reserve_config = ReserveConfig {
+                optimal_utilization_rate: value_t_or_exit!(
+                    arg_matches,
+                    "optimal_utilization_rate",
+                    u8
+                ),
+                loan_to_value_ratio: value_t_or_exit!(arg_matches, "loan_to_value_ratio", u8),
+                liquidation_bonus: value_t_or_exit!(arg_matches, "liquidation_bonus", u8),
+                liquidation_threshold: value_t_or_exit!(arg_matches, "liquidation_threshold", u8),
+                min_liquidation_close_factor: value_t_or_exit!(
+                    arg_matches,
+                    "min_liquidation_close_factor",
+                    u8
+                ),
+                max_liquidation_close_factor: value_t_or_exit!(
+                    arg_matches,
+                    "max_liquidation_close_factor",
+                    u8
+                ),
+                base_borrow_rate: value_t_or_exit!(arg_matches, "base_borrow_rate", u8),
+                borrow_rate_slope_1: value_t_or_exit!(arg_matches, "borrow_rate_slope_1", u8),
+                borrow_rate_slope_2: value_t_or_exit!(arg_matches, "borrow_rate_slope_2", u8),
+                deposit_limit: value_t!(arg_matches, "deposit_limit", u64).unwrap_or(0),
+                borrow_limit: value_t!(arg_matches, "borrow_limit", u64).unwrap_or(0),
+                fees: ReserveFees {
+                    borrow_fee_wad: value_t_or_exit!(arg_matches, "borrow_fee_wad", u64),
+                    host_fee_percentage: value_t_or_exit!(arg_matches, "host_fee_percentage", u8),
+                    protocol_take_rate: value_t_or_exit!(arg_matches, "protocol_take_rate", u8),
+                },
+            };
+            command_add_reserve(
+                &config,
+                &program_id,
+                &lending_market,
+                &source,
+                amount,
+                reserve_config,
+                dex_market,
+                oracle,
+            )
+        }
+        ("deposit", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_deposit(
+                &config,
+                &program_id,
+                &reserve,
+                &source,
+                &destination,
+                amount,
+            )
+        }
+        ("borrow", Some(arg_matches)) => {
+            let deposit_reserve = pubkey_of(arg_matches, "deposit_reserve").unwrap();
+            let borrow_reserve = pubkey_of(arg_matches, "borrow_reserve").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let amount_type = if arg_matches.is_present("collateral_amount") {
+                BorrowAmountType::CollateralDepositAmount
+            } else {
+                BorrowAmountType::LiquidityBorrowAmount
+            };
+            let obligation_accounts = pubkey_of(arg_matches, "obligation")
+                .zip(pubkey_of(arg_matches, "obligation_token_account"));
+            command_borrow(
+                &config,
+                &program_id,
+                &deposit_reserve,
+                &borrow_reserve,
+                &source,
+                &destination,
+                amount,
+                amount_type,
+                obligation_accounts,
+            )
+        }
+        ("repay", Some(arg_matches)) => {
+            let obligation = pubkey_of(arg_matches, "obligation").unwrap();
+            let obligation_token_account =
+                pubkey_of(arg_matches, "obligation_token_account").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let amount = match arg_matches.value_of("amount").unwrap() {
+                "ALL" => None,
+                _ => Some(value_t_or_exit!(arg_matches, "amount", f64)),
+            };
+            command_repay(
+                &config,
+                &program_id,
+                &obligation,
+                &obligation_token_account,
+                &source,
+                &destination,
+                amount,
+            )
+        }
+        ("liquidate", Some(arg_matches)) => {
+            let obligation = pubkey_of(arg_matches, "obligation").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_liquidate(
+                &config,
+                &program_id,
+                &obligation,
+                &source,
+                &destination,
+                amount,
+            )
+        }
+        ("show-reserve", Some(arg_matches)) => {
+            let reserve = pubkey_of(arg_matches, "reserve").unwrap();
+            command_show_reserve(&config, &reserve)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
@@ -56,12 +56,17 @@ pub fn main() {
         loan_to_value_ratio: 75,
         liquidation_bonus: 5,
         liquidation_threshold: 80,
-        min_borrow_rate: 0,
-        optimal_borrow_rate: 4,
-        max_borrow_rate: 30,
+        min_liquidation_close_factor: 50,
+        max_liquidation_close_factor: 100,
+        base_borrow_rate: 0,
+        borrow_rate_slope_1: 4,
+        borrow_rate_slope_2: 26,
+        deposit_limit: u64::MAX,
+        borrow_limit: u64::MAX,
         fees: ReserveFees {
             borrow_fee_wad: 100_000_000_000_000, // 1 bp
             host_fee_percentage: 20,
+            protocol_take_rate: 10,
         },
     };
 
@@ -83,12 +88,17 @@ pub fn main() {
         loan_to_value_ratio: 75,
         liquidation_bonus: 10,
         liquidation_threshold: 80,
-        min_borrow_rate: 0,
-        optimal_borrow_rate: 2,
-        max_borrow_rate: 15,
+        min_liquidation_close_factor: 50,
+        max_liquidation_close_factor: 100,
+        base_borrow_rate: 0,
+        borrow_rate_slope_1: 2,
+        borrow_rate_slope_2: 13,
+        deposit_limit: u64::MAX,
+        borrow_limit: u64::MAX,
         fees: ReserveFees {
             borrow_fee_wad: 1_000_000_000_000, // 0.01 bp
             host_fee_percentage: 20,
+            protocol_take_rate: 10,
         },
     };
 
@@ -110,12 +120,17 @@ pub fn main() {
         loan_to_value_ratio: 75,
         liquidation_bonus: 10,
         liquidation_threshold: 80,
-        min_borrow_rate: 0,
-        optimal_borrow_rate: 2,
-        max_borrow_rate: 15,
+        min_liquidation_close_factor: 50,
+        max_liquidation_close_factor: 100,
+        base_borrow_rate: 0,
+        borrow_rate_slope_1: 2,
+        borrow_rate_slope_2: 13,
+        deposit_limit: u64::MAX,
+        borrow_limit: u64::MAX,
         fees: ReserveFees {
             borrow_fee_wad: 10_000_000_000_000, // 0.1 bp
             host_fee_percentage: 25,
+            protocol_take_rate: 10,
         },
     };
 
@@ -182,6 +197,7 @@ pub fn create_reserve(
     let collateral_supply_keypair = Keypair::new();
     let collateral_fees_receiver_keypair = Keypair::new();
     let liquidity_supply_keypair = Keypair::new();
+    let liquidity_fees_receiver_keypair = Keypair::new();
     let user_collateral_token_keypair = Keypair::new();
     let user_transfer_authority = Keypair::new();
 
@@ -226,6 +242,13 @@ pub fn create_reserve(
                 Token::LEN as u64,
                 &spl_token::id(),
             ),
+            create_account(
+                &payer.pubkey(),
+                &liquidity_fees_receiver_keypair.pubkey(),
+                token_balance,
+                Token::LEN as u64,
+                &spl_token::id(),
+            ),
             create_account(
                 &payer.pubkey(),
                 &user_collateral_token_keypair.pubkey(),
@@ -252,7 +275,9 @@ pub fn create_reserve(
             &reserve_keypair,
             &collateral_mint_keypair,
             &collateral_supply_keypair,
+            &collateral_fees_receiver_keypair,
             &liquidity_supply_keypair,
+            &liquidity_fees_receiver_keypair,
             &user_collateral_token_keypair,
         ],
         recent_blockhash,
@@ -283,10 +308,12 @@ pub fn create_reserve(
                 collateral_mint_keypair.pubkey(),
                 collateral_supply_keypair.pubkey(),
                 collateral_fees_receiver_keypair.pubkey(),
+                liquidity_fees_receiver_keypair.pubkey(),
                 lending_market_pubkey,
                 lending_market_owner.pubkey(),
                 user_transfer_authority.pubkey(),
                 dex_market_pubkey,
+                None,
             ),
         ],
         Some(&payer.pubkey()),
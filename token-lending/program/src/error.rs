@@ -136,6 +136,29 @@ pub enum LendingError {
     /// Obligation collateral cannot be withdrawn below required amount
     #[error("Obligation collateral cannot be withdrawn below required amount")]
     ObligationCollateralWithdrawBelowRequired,
+    /// Obligation state stale
+    #[error("Obligation state needs to be refreshed for the current slot")]
+    ObligationStale,
+    /// Oracle account is not a recognized price feed
+    #[error("Input oracle account is not valid")]
+    InvalidOracleConfig,
+
+    // 40
+    /// Oracle price has not been updated recently
+    #[error("Oracle price is stale")]
+    OraclePriceStale,
+    /// Oracle confidence interval is too wide relative to the price
+    #[error("Oracle price confidence interval is too wide")]
+    OraclePriceUncertain,
+    /// Deposit would exceed the reserve deposit limit
+    #[error("Reserve deposit limit exceeded")]
+    ReserveDepositLimitExceeded,
+    /// Borrow would exceed the reserve borrow limit
+    #[error("Reserve borrow limit exceeded")]
+    ReserveBorrowLimitExceeded,
+    /// Obligation still has collateral or a loan balance above the dust amount
+    #[error("Obligation has collateral or borrows remaining")]
+    ObligationNotEmpty,
 }
 
 impl From<LendingError> for ProgramError {
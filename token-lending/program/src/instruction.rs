@@ -23,6 +23,50 @@ pub enum BorrowAmountType {
     CollateralDepositAmount,
 }
 
+/// Accounts used to price reserve liquidity in a common currency
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriceAccounts {
+    /// Price by the best order of a dex market
+    DexMarket {
+        /// Dex market
+        dex_market_pubkey: Pubkey,
+        /// Dex market order book side
+        dex_market_order_book_side_pubkey: Pubkey,
+        /// Temporary memory owned by the lending program
+        memory_pubkey: Pubkey,
+    },
+    /// Price by the oracles of both reserves
+    Oracles {
+        /// Deposit or withdraw reserve price oracle
+        collateral_reserve_oracle_pubkey: Pubkey,
+        /// Borrow or repay reserve price oracle
+        borrow_reserve_oracle_pubkey: Pubkey,
+    },
+}
+
+impl PriceAccounts {
+    fn to_account_metas(self) -> Vec<AccountMeta> {
+        match self {
+            Self::DexMarket {
+                dex_market_pubkey,
+                dex_market_order_book_side_pubkey,
+                memory_pubkey,
+            } => vec![
+                AccountMeta::new_readonly(dex_market_pubkey, false),
+                AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
+                AccountMeta::new_readonly(memory_pubkey, false),
+            ],
+            Self::Oracles {
+                collateral_reserve_oracle_pubkey,
+                borrow_reserve_oracle_pubkey,
+            } => vec![
+                AccountMeta::new_readonly(collateral_reserve_oracle_pubkey, false),
+                AccountMeta::new_readonly(borrow_reserve_oracle_pubkey, false),
+            ],
+        }
+    }
+}
+
 /// Instructions supported by the lending program.
 #[derive(Clone, Debug, PartialEq)]
 pub enum LendingInstruction {
@@ -53,15 +97,20 @@ pub enum LendingInstruction {
     ///   5. `[writable]` Reserve collateral SPL Token mint - uninitialized
     ///   6. `[writable]` Reserve collateral token supply - uninitialized
     ///   7. `[writable]` Reserve collateral fees receiver - uninitialized.
-    ///                     Owner will be set to the lending market account.
-    ///   8. `[]` Lending market account.
-    ///   9. `[signer]` Lending market owner.
-    ///   10 `[]` Derived lending market authority.
-    ///   11 `[]` User transfer authority ($authority).
-    ///   12 `[]` Clock sysvar
-    ///   13 `[]` Rent sysvar
-    ///   14 '[]` Token program id
-    ///   15 `[optional]` Serum DEX market account. Not required for quote currency reserves. Must be initialized and match quote and base currency.
+    ///                     Owner will be set to the lending market owner.
+    ///   8. `[writable]` Reserve liquidity fees receiver - uninitialized.
+    ///                     Owner will be set to the derived lending market authority.
+    ///   9. `[]` Lending market account.
+    ///   10 `[signer]` Lending market owner.
+    ///   11 `[]` Derived lending market authority.
+    ///   12 `[signer]` User transfer authority ($authority).
+    ///   13 `[]` Clock sysvar
+    ///   14 `[]` Rent sysvar
+    ///   15 '[]` Token program id
+    ///   16 `[]` Serum DEX market account. Omitted for quote currency reserves. Must be initialized and match quote and base currency.
+    ///   16 or 17 `[optional]` Pyth or Switchboard price oracle account, following the dex market account
+    ///                     if there is one. Reserves are only priced by their oracles when both the deposit
+    ///                     and borrow reserve of an obligation have one.
     InitReserve {
         /// Initial amount of liquidity to deposit into the new reserve
         liquidity_amount: u64,
@@ -79,7 +128,7 @@ pub enum LendingInstruction {
     ///   2. `[writable]` Obligation
     ///   3. `[writable]` Obligation token mint
     ///   4. `[writable]` Obligation token output
-    ///   5. `[]` Obligation token owner
+    ///   5. `[]` Obligation owner. Owns the obligation token output and can close the obligation.
     ///   6. `[]` Lending market account.
     ///   7. `[]` Derived lending market authority.
     ///   8. `[]` Clock sysvar
@@ -149,11 +198,18 @@ pub enum LendingInstruction {
     ///   10 `[]` Lending market account.
     ///   11 `[]` Derived lending market authority.
     ///   12 `[signer]` User transfer authority ($authority).
-    ///   13 `[]` Dex market
-    ///   14 `[]` Dex market order book side
-    ///   15 `[]` Temporary memory
-    ///   16 `[]` Clock sysvar
-    ///   17 '[]` Token program id
+    ///   13 `[]` Clock sysvar
+    ///   14 '[]` Token program id
+    ///
+    ///   Oracle pricing, if both reserves have an oracle:
+    ///   15 `[]` Deposit reserve price oracle account.
+    ///   16 `[]` Borrow reserve price oracle account.
+    ///   17 `[optional, writable]` Deposit reserve collateral host fee receiver account.
+    ///
+    ///   Dex market pricing, if neither reserve has an oracle:
+    ///   15 `[]` Dex market
+    ///   16 `[]` Dex market order book side
+    ///   17 `[]` Temporary memory
     ///   18 `[optional, writable]` Deposit reserve collateral host fee receiver account.
     BorrowReserveLiquidity {
         // TODO: slippage constraint
@@ -205,11 +261,8 @@ pub enum LendingInstruction {
     ///   7. `[]` Lending market account.
     ///   8. `[]` Derived lending market authority.
     ///   9. `[signer]` User transfer authority ($authority).
-    ///   10 `[]` Dex market
-    ///   11 `[]` Dex market order book side
-    ///   12 `[]` Temporary memory
-    ///   13 `[]` Clock sysvar
-    ///   14 `[]` Token program id
+    ///   10 `[]` Clock sysvar
+    ///   11 `[]` Token program id
     LiquidateObligation {
         /// Amount of loan to repay
         liquidity_amount: u64,
@@ -262,11 +315,8 @@ pub enum LendingInstruction {
     ///   7. `[]` Lending market account.
     ///   8. `[]` Derived lending market authority.
     ///   9. `[signer]` User transfer authority ($authority).
-    ///   10 `[]` Dex market
-    ///   11 `[]` Dex market order book side
-    ///   12 `[]` Temporary memory
-    ///   13 `[]` Clock sysvar
-    ///   14 '[]` Token program id
+    ///   10 `[]` Clock sysvar
+    ///   11 '[]` Token program id
     WithdrawObligationCollateral {
         /// Amount of collateral to withdraw
         collateral_amount: u64,
@@ -283,6 +333,73 @@ pub enum LendingInstruction {
         /// The new owner
         new_owner: Pubkey,
     },
+
+    // 12
+    /// Accrue interest on an obligation and update the market value of its collateral and loan.
+    /// Borrow, withdraw obligation collateral and liquidate use the values stored by a refresh
+    /// in the same slot, after which the obligation balances must not have changed.
+    ///
+    /// Reserves which both have an oracle are priced by their oracles, otherwise by the dex
+    /// market of the reserve which is not the quote currency. Fails with `InvalidOracleConfig`
+    /// if only one of the reserves has an oracle.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation
+    ///   1. `[]` Deposit reserve account.
+    ///   2. `[]` Borrow reserve account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Clock sysvar
+    ///
+    ///   Oracle pricing:
+    ///   5. `[]` Deposit reserve price oracle account.
+    ///   6. `[]` Borrow reserve price oracle account.
+    ///
+    ///   Dex market pricing:
+    ///   5. `[]` Dex market
+    ///   6. `[]` Dex market order book side
+    ///   7. `[]` Temporary memory
+    RefreshObligation,
+
+    // 13
+    /// Credit the protocol share of accrued interest from a reserve to its liquidity fees
+    /// receiver, then pay out the receiver balance to the current lending market owner. Fees are
+    /// redeemed up to the reserve's available liquidity.
+    ///
+    /// Borrow origination fees are not included, they are paid in deposit reserve collateral
+    /// to the collateral fees receiver when borrowing.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Reserve liquidity fees receiver.
+    ///   3. `[writable]` Destination liquidity token account, owned by the lending market owner.
+    ///   4. `[]` Lending market account.
+    ///   5. `[signer]` Lending market owner.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[]` Clock sysvar
+    ///   8. '[]` Token program id
+    RedeemFees,
+
+    // 14
+    /// Close an empty obligation and return its rent to the destination account. Any remaining
+    /// loan balance below the closeable amount is written off and removed from the borrow
+    /// reserve's borrowed amount, so the loss is shared by the borrow reserve's depositors.
+    /// This is the only instruction which forgives a loan balance.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation
+    ///   1. `[writable]` Borrow reserve account.
+    ///   2. `[]` Obligation token mint
+    ///   3. `[]` Obligation token input. Must hold the entire obligation token supply.
+    ///   4. `[signer]` Obligation owner, set when the obligation was initialized. Obligations
+    ///                   without a recorded owner accept the obligation token input owner.
+    ///   5. `[writable]` Destination account for the obligation rent lamports.
+    ///   6. `[]` Lending market account.
+    ///   7. `[]` Clock sysvar
+    CloseObligation,
 }
 
 impl LendingInstruction {
@@ -302,11 +419,16 @@ impl LendingInstruction {
                 let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
                 let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
                 let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
-                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (min_liquidation_close_factor, rest) = Self::unpack_u8(rest)?;
+                let (max_liquidation_close_factor, rest) = Self::unpack_u8(rest)?;
+                let (base_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (borrow_rate_slope_1, rest) = Self::unpack_u8(rest)?;
+                let (borrow_rate_slope_2, rest) = Self::unpack_u8(rest)?;
+                let (deposit_limit, rest) = Self::unpack_u64(rest)?;
+                let (borrow_limit, rest) = Self::unpack_u64(rest)?;
                 let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (host_fee_percentage, _rest) = Self::unpack_u8(rest)?;
+                let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+                let (protocol_take_rate, _rest) = Self::unpack_u8(rest)?;
                 Self::InitReserve {
                     liquidity_amount,
                     config: ReserveConfig {
@@ -314,12 +436,17 @@ impl LendingInstruction {
                         loan_to_value_ratio,
                         liquidation_bonus,
                         liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
+                        min_liquidation_close_factor,
+                        max_liquidation_close_factor,
+                        base_borrow_rate,
+                        borrow_rate_slope_1,
+                        borrow_rate_slope_2,
+                        deposit_limit,
+                        borrow_limit,
                         fees: ReserveFees {
                             borrow_fee_wad,
                             host_fee_percentage,
+                            protocol_take_rate,
                         },
                     },
                 }
@@ -364,6 +491,9 @@ impl LendingInstruction {
                 let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
                 Self::SetLendingMarketOwner { new_owner }
             }
+            12 => Self::RefreshObligation,
+            13 => Self::RedeemFees,
+            14 => Self::CloseObligation,
             _ => return Err(LendingError::InstructionUnpackError.into()),
         })
     }
@@ -422,13 +552,18 @@ impl LendingInstruction {
                         loan_to_value_ratio,
                         liquidation_bonus,
                         liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
+                        min_liquidation_close_factor,
+                        max_liquidation_close_factor,
+                        base_borrow_rate,
+                        borrow_rate_slope_1,
+                        borrow_rate_slope_2,
+                        deposit_limit,
+                        borrow_limit,
                         fees:
                             ReserveFees {
                                 borrow_fee_wad,
                                 host_fee_percentage,
+                                protocol_take_rate,
                             },
                     },
             } => {
@@ -438,11 +573,16 @@ impl LendingInstruction {
                 buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
                 buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
                 buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&min_liquidation_close_factor.to_le_bytes());
+                buf.extend_from_slice(&max_liquidation_close_factor.to_le_bytes());
+                buf.extend_from_slice(&base_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&borrow_rate_slope_1.to_le_bytes());
+                buf.extend_from_slice(&borrow_rate_slope_2.to_le_bytes());
+                buf.extend_from_slice(&deposit_limit.to_le_bytes());
+                buf.extend_from_slice(&borrow_limit.to_le_bytes());
                 buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
                 buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
+                buf.extend_from_slice(&protocol_take_rate.to_le_bytes());
             }
             Self::InitObligation => {
                 buf.push(2);
@@ -486,6 +626,15 @@ impl LendingInstruction {
                 buf.push(11);
                 buf.extend_from_slice(new_owner.as_ref());
             }
+            Self::RefreshObligation => {
+                buf.push(12);
+            }
+            Self::RedeemFees => {
+                buf.push(13);
+            }
+            Self::CloseObligation => {
+                buf.push(14);
+            }
         }
         buf
     }
@@ -527,10 +676,12 @@ pub fn init_reserve(
     reserve_collateral_mint_pubkey: Pubkey,
     reserve_collateral_supply_pubkey: Pubkey,
     reserve_collateral_fees_receiver_pubkey: Pubkey,
+    reserve_liquidity_fees_receiver_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     lending_market_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
     dex_market_pubkey: Option<Pubkey>,
+    oracle_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) =
         Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
@@ -543,6 +694,7 @@ pub fn init_reserve(
         AccountMeta::new(reserve_collateral_mint_pubkey, false),
         AccountMeta::new(reserve_collateral_supply_pubkey, false),
         AccountMeta::new(reserve_collateral_fees_receiver_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fees_receiver_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_owner_pubkey, true),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
@@ -555,6 +707,9 @@ pub fn init_reserve(
     if let Some(dex_market_pubkey) = dex_market_pubkey {
         accounts.push(AccountMeta::new_readonly(dex_market_pubkey, false));
     }
+    if let Some(oracle_pubkey) = oracle_pubkey {
+        accounts.push(AccountMeta::new_readonly(oracle_pubkey, false));
+    }
 
     Instruction {
         program_id,
@@ -684,9 +839,7 @@ pub fn borrow_reserve_liquidity(
     obligation_pubkey: Pubkey,
     obligation_token_mint_pubkey: Pubkey,
     obligation_token_output_pubkey: Pubkey,
-    dex_market_pubkey: Pubkey,
-    dex_market_order_book_side_pubkey: Pubkey,
-    memory_pubkey: Pubkey,
+    price_accounts: PriceAccounts,
     deposit_reserve_collateral_host_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let mut accounts = vec![
@@ -703,12 +856,10 @@ pub fn borrow_reserve_liquidity(
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
         AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-        AccountMeta::new_readonly(dex_market_pubkey, false),
-        AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
-        AccountMeta::new_readonly(memory_pubkey, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    accounts.extend(price_accounts.to_account_metas());
     if let Some(deposit_reserve_collateral_host_pubkey) = deposit_reserve_collateral_host_pubkey {
         accounts.push(AccountMeta::new(
             deposit_reserve_collateral_host_pubkey,
@@ -781,9 +932,6 @@ pub fn liquidate_obligation(
     lending_market_pubkey: Pubkey,
     lending_market_authority_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
-    dex_market_pubkey: Pubkey,
-    dex_market_order_book_side_pubkey: Pubkey,
-    memory_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
@@ -798,9 +946,6 @@ pub fn liquidate_obligation(
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(dex_market_pubkey, false),
-            AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
-            AccountMeta::new_readonly(memory_pubkey, false),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
@@ -871,9 +1016,6 @@ pub fn withdraw_obligation_collateral(
     lending_market_pubkey: Pubkey,
     lending_market_authority_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
-    dex_market_pubkey: Pubkey,
-    dex_market_order_book_side_pubkey: Pubkey,
-    memory_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
@@ -888,9 +1030,6 @@ pub fn withdraw_obligation_collateral(
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(dex_market_pubkey, false),
-            AccountMeta::new_readonly(dex_market_order_book_side_pubkey, false),
-            AccountMeta::new_readonly(memory_pubkey, false),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
@@ -914,3 +1053,84 @@ pub fn set_lending_market_owner(
         data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
     }
 }
+
+/// Creates a 'RefreshObligation' instruction.
+pub fn refresh_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    price_accounts: PriceAccounts,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(deposit_reserve_pubkey, false),
+        AccountMeta::new_readonly(borrow_reserve_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(price_accounts.to_account_metas());
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshObligation.pack(),
+    }
+}
+
+/// Creates a 'RedeemFees' instruction.
+pub fn redeem_fees(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_liquidity_fees_receiver_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) =
+        Pubkey::find_program_address(&[&lending_market_pubkey.to_bytes()[..32]], &program_id);
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_liquidity_fees_receiver_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::RedeemFees.pack(),
+    }
+}
+
+/// Creates a 'CloseObligation' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn close_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    obligation_token_mint_pubkey: Pubkey,
+    obligation_token_input_pubkey: Pubkey,
+    obligation_token_owner_pubkey: Pubkey,
+    destination_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(borrow_reserve_pubkey, false),
+            AccountMeta::new_readonly(obligation_token_mint_pubkey, false),
+            AccountMeta::new_readonly(obligation_token_input_pubkey, false),
+            AccountMeta::new_readonly(obligation_token_owner_pubkey, true),
+            AccountMeta::new(destination_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::CloseObligation.pack(),
+    }
+}
@@ -0,0 +1,138 @@
+//! Interest rate models
+
+use crate::math::{common::*, rate::Rate};
+use solana_program::program_error::ProgramError;
+
+/// Kinked borrow rate model.
+///
+/// The borrow rate rises linearly from the base rate by `slope_1` as utilization goes from zero
+/// to the optimal utilization rate, then by a further `slope_2` as utilization goes from the
+/// optimal utilization rate to 100%.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KinkedRateModel {
+    /// Borrow rate at zero utilization
+    pub base_rate: Rate,
+    /// Utilization rate at which the second slope takes effect
+    pub optimal_utilization_rate: Rate,
+    /// Borrow rate increase from zero to optimal utilization
+    pub slope_1: Rate,
+    /// Borrow rate increase from optimal to full utilization
+    pub slope_2: Rate,
+}
+
+impl KinkedRateModel {
+    /// Borrow rate for the given utilization rate
+    pub fn borrow_rate(&self, utilization_rate: Rate) -> Result<Rate, ProgramError> {
+        let optimal_rate = self.base_rate.try_add(self.slope_1)?;
+        if utilization_rate < self.optimal_utilization_rate {
+            let normalized_rate = utilization_rate.try_div(self.optimal_utilization_rate)?;
+            normalized_rate
+                .try_mul(self.slope_1)?
+                .try_add(self.base_rate)
+        } else if self.optimal_utilization_rate == Rate::one() {
+            Ok(optimal_rate)
+        } else {
+            let normalized_rate = utilization_rate
+                .try_sub(self.optimal_utilization_rate)?
+                .try_div(Rate::one().try_sub(self.optimal_utilization_rate)?)?;
+            normalized_rate.try_mul(self.slope_2)?.try_add(optimal_rate)
+        }
+    }
+}
+
+/// Compound an annual rate over elapsed periods, returning the growth multiplier
+pub fn compound_rate(
+    annual_rate: Rate,
+    periods_per_year: u64,
+    periods_elapsed: u64,
+) -> Result<Rate, ProgramError> {
+    let period_rate = annual_rate.try_div(periods_per_year)?;
+    Rate::one().try_add(period_rate)?.try_pow(periods_elapsed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const PERIODS_PER_YEAR: u64 = 1_000;
+
+    fn rate_model(
+        base_rate: u8,
+        optimal_utilization_rate: u8,
+        slope_1: u8,
+        slope_2: u8,
+    ) -> KinkedRateModel {
+        KinkedRateModel {
+            base_rate: Rate::from_percent(base_rate),
+            optimal_utilization_rate: Rate::from_percent(optimal_utilization_rate),
+            slope_1: Rate::from_percent(slope_1),
+            slope_2: Rate::from_percent(slope_2),
+        }
+    }
+
+    // Creates utilization rates (lower, higher) where 0 <= lower <= higher <= 1
+    prop_compose! {
+        fn utilization_rates()(higher in 0..=WAD)(
+            lower in 0..=higher,
+            higher in Just(higher),
+        ) -> (Rate, Rate) {
+            (Rate::from_scaled_val(lower), Rate::from_scaled_val(higher))
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn borrow_rate_is_monotonic(
+            base_rate in 0..=u8::MAX,
+            optimal_utilization_rate in 0..=100u8,
+            slope_1 in 0..=u8::MAX,
+            slope_2 in 0..=u8::MAX,
+            (lower_utilization, higher_utilization) in utilization_rates(),
+        ) {
+            let model = rate_model(base_rate, optimal_utilization_rate, slope_1, slope_2);
+            let lower_rate = model.borrow_rate(lower_utilization)?;
+            let higher_rate = model.borrow_rate(higher_utilization)?;
+            assert!(lower_rate <= higher_rate);
+        }
+
+        #[test]
+        fn borrow_rate_bounds(
+            base_rate in 0..=u8::MAX,
+            optimal_utilization_rate in 0..=100u8,
+            slope_1 in 0..=u8::MAX,
+            slope_2 in 0..=u8::MAX,
+            utilization_rate in 0..=WAD,
+        ) {
+            let model = rate_model(base_rate, optimal_utilization_rate, slope_1, slope_2);
+            let borrow_rate = model.borrow_rate(Rate::from_scaled_val(utilization_rate))?;
+            let optimal_rate = model.base_rate.try_add(model.slope_1)?;
+            let max_rate = optimal_rate.try_add(model.slope_2)?;
+
+            assert!(borrow_rate >= model.base_rate);
+            assert!(borrow_rate <= max_rate);
+            assert_eq!(model.borrow_rate(Rate::zero())?, if optimal_utilization_rate == 0 {
+                optimal_rate
+            } else {
+                model.base_rate
+            });
+            assert_eq!(model.borrow_rate(model.optimal_utilization_rate)?, optimal_rate);
+        }
+
+        #[test]
+        fn compound_rate_is_monotonic(
+            lower_rate in 0..=u8::MAX,
+            rate_increase in 0..=u8::MAX,
+            periods_elapsed in 0..=PERIODS_PER_YEAR,
+        ) {
+            let higher_rate = lower_rate.saturating_add(rate_increase);
+            let lower_growth = compound_rate(Rate::from_percent(lower_rate), PERIODS_PER_YEAR, periods_elapsed)?;
+            let higher_growth = compound_rate(Rate::from_percent(higher_rate), PERIODS_PER_YEAR, periods_elapsed)?;
+            let later_growth = compound_rate(Rate::from_percent(lower_rate), PERIODS_PER_YEAR, periods_elapsed + 1)?;
+
+            assert!(lower_growth >= Rate::one());
+            assert!(lower_growth <= higher_growth);
+            assert!(lower_growth <= later_growth);
+        }
+    }
+}
@@ -2,8 +2,10 @@
 
 mod common;
 mod decimal;
+mod interest;
 mod rate;
 
 pub use common::*;
 pub use decimal::*;
+pub use interest::*;
 pub use rate::*;
@@ -3,7 +3,7 @@
 //! Usages and their ranges include:
 //!   - Collateral exchange ratio <= 5.0
 //!   - Loan to value ratio <= 0.9
-//!   - Max borrow rate <= 2.56
+//!   - Max borrow rate <= 7.65
 //!   - Percentages <= 1.0
 //!
 //! Rates are internally scaled by a WAD (10^18) to preserve
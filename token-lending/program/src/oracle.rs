@@ -0,0 +1,397 @@
+//! Price oracles used for valuing reserve liquidity
+
+use crate::{
+    error::LendingError,
+    math::{Decimal, TryDiv, TryMul},
+    state::{Reserve, TokenConverter},
+};
+use arrayref::{array_ref, array_refs};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::{Clock, Slot},
+    msg,
+    program_error::ProgramError,
+    program_option::COption,
+    pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+/// Pyth oracle program
+pub mod pyth {
+    solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+/// Switchboard oracle program
+pub mod switchboard {
+    solana_program::declare_id!("DtmE9D2CSB4L5D6A15mraeEjrGMm6auWVzgaD8hK2tZM");
+}
+
+/// Number of slots after which an oracle price is no longer used
+pub const STALE_AFTER_SLOTS_ELAPSED: Slot = 240;
+
+/// Maximum width of an oracle confidence interval as a percentage of the price
+pub const MAX_CONFIDENCE_PERCENT: u64 = 2;
+
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_VERSION: u32 = 2;
+const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_PRICE_LEN: usize = 240;
+
+const SWITCHBOARD_RESULT_ACCOUNT_TYPE: u8 = 3;
+const SWITCHBOARD_RESULT_LEN: usize = 81;
+const SWITCHBOARD_PRICE_SCALE: u64 = 1_000_000_000;
+
+/// Price reported by an oracle feed
+#[derive(Clone, Debug, PartialEq)]
+pub struct OraclePrice {
+    /// Market price of one whole token
+    pub price: Decimal,
+    /// Half-width of the confidence interval around the price
+    pub confidence: Decimal,
+    /// Slot when the price was published
+    pub publish_slot: Slot,
+}
+
+impl OraclePrice {
+    /// Reject prices which are stale or too uncertain to value an obligation
+    pub fn validate(&self, clock: &Clock) -> Result<Decimal, ProgramError> {
+        if clock.slot.saturating_sub(self.publish_slot) > STALE_AFTER_SLOTS_ELAPSED {
+            msg!(
+                "Oracle price published at slot {} is stale",
+                self.publish_slot
+            );
+            return Err(LendingError::OraclePriceStale.into());
+        }
+        if self.price == Decimal::zero() {
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        if self.confidence.try_mul(100)? > self.price.try_mul(MAX_CONFIDENCE_PERCENT)? {
+            return Err(LendingError::OraclePriceUncertain.into());
+        }
+        Ok(self.price)
+    }
+}
+
+/// Load the validated price of one whole token from an oracle account, dispatching on the
+/// program which owns the account
+pub fn get_oracle_price(oracle_info: &AccountInfo, clock: &Clock) -> Result<Decimal, ProgramError> {
+    let data = &oracle_info.data.borrow();
+    let oracle_price = if oracle_info.owner == &pyth::id() {
+        unpack_pyth_price(data)?
+    } else if oracle_info.owner == &switchboard::id() {
+        unpack_switchboard_price(data)?
+    } else {
+        msg!("Oracle account is not owned by a supported oracle program");
+        return Err(LendingError::InvalidOracleConfig.into());
+    };
+    oracle_price.validate(clock)
+}
+
+/// Unpack the aggregate price from a Pyth price account
+pub fn unpack_pyth_price(data: &[u8]) -> Result<OraclePrice, ProgramError> {
+    if data.len() < PYTH_PRICE_LEN {
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    let input = array_ref![data, 0, PYTH_PRICE_LEN];
+    #[allow(clippy::ptr_offset_with_cast)]
+    let (
+        magic,
+        version,
+        account_type,
+        _size,
+        _price_type,
+        exponent,
+        _price_data,
+        price,
+        confidence,
+        status,
+        _corporate_action,
+        publish_slot,
+    ) = array_refs![input, 4, 4, 4, 4, 4, 4, 184, 8, 8, 4, 4, 8];
+
+    if u32::from_le_bytes(*magic) != PYTH_MAGIC
+        || u32::from_le_bytes(*version) != PYTH_VERSION
+        || u32::from_le_bytes(*account_type) != PYTH_PRICE_ACCOUNT_TYPE
+    {
+        msg!("Oracle account is not a Pyth price account");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if u32::from_le_bytes(*status) != PYTH_STATUS_TRADING {
+        msg!("Pyth price is not currently trading");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    let price =
+        u64::try_from(i64::from_le_bytes(*price)).map_err(|_| LendingError::InvalidOracleConfig)?;
+    let exponent = i32::from_le_bytes(*exponent);
+    Ok(OraclePrice {
+        price: scale_by_exponent(price, exponent)?,
+        confidence: scale_by_exponent(u64::from_le_bytes(*confidence), exponent)?,
+        publish_slot: u64::from_le_bytes(*publish_slot),
+    })
+}
+
+/// Unpack the latest round result from a Switchboard aggregator result account
+pub fn unpack_switchboard_price(data: &[u8]) -> Result<OraclePrice, ProgramError> {
+    if data.len() < SWITCHBOARD_RESULT_LEN {
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    let input = array_ref![data, 0, SWITCHBOARD_RESULT_LEN];
+    #[allow(clippy::ptr_offset_with_cast)]
+    let (
+        account_type,
+        _parent,
+        num_success,
+        _num_error,
+        result,
+        round_open_slot,
+        _round_open_timestamp,
+        min_response,
+        max_response,
+    ) = array_refs![input, 1, 32, 4, 4, 8, 8, 8, 8, 8];
+
+    if account_type[0] != SWITCHBOARD_RESULT_ACCOUNT_TYPE {
+        msg!("Oracle account is not a Switchboard aggregator result account");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if i32::from_le_bytes(*num_success) <= 0 {
+        msg!("Switchboard round has no successful responses");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    let min_response = f64::from_le_bytes(*min_response);
+    let max_response = f64::from_le_bytes(*max_response);
+    Ok(OraclePrice {
+        price: decimal_from_f64(f64::from_le_bytes(*result))?,
+        confidence: decimal_from_f64((max_response - min_response) / 2.0)?,
+        publish_slot: u64::from_le_bytes(*round_open_slot),
+    })
+}
+
+fn scale_by_exponent(value: u64, exponent: i32) -> Result<Decimal, ProgramError> {
+    let scale = exponent
+        .checked_abs()
+        .and_then(|exponent| 10u64.checked_pow(exponent as u32))
+        .ok_or(LendingError::MathOverflow)?;
+    if exponent < 0 {
+        Decimal::from(value).try_div(scale)
+    } else {
+        Decimal::from(value).try_mul(scale)
+    }
+}
+
+fn decimal_from_f64(value: f64) -> Result<Decimal, ProgramError> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    let scaled_value = value * SWITCHBOARD_PRICE_SCALE as f64;
+    if scaled_value >= u64::MAX as f64 {
+        return Err(LendingError::MathOverflow.into());
+    }
+    Decimal::from(scaled_value as u64).try_div(SWITCHBOARD_PRICE_SCALE)
+}
+
+/// Token converter which values two reserve liquidity tokens using oracle prices
+pub struct OracleConverter<'a> {
+    token_mint_a: &'a Pubkey,
+    unit_price_a: Decimal,
+    token_mint_b: &'a Pubkey,
+    unit_price_b: Decimal,
+}
+
+impl<'a> OracleConverter<'a> {
+    /// Create a new OracleConverter from two reserves and their configured oracle accounts
+    pub fn new(
+        reserve_a: &'a Reserve,
+        oracle_a_info: &AccountInfo,
+        reserve_b: &'a Reserve,
+        oracle_b_info: &AccountInfo,
+        clock: &Clock,
+    ) -> Result<Self, ProgramError> {
+        Ok(Self {
+            token_mint_a: &reserve_a.liquidity.mint_pubkey,
+            unit_price_a: Self::unit_price(reserve_a, oracle_a_info, clock)?,
+            token_mint_b: &reserve_b.liquidity.mint_pubkey,
+            unit_price_b: Self::unit_price(reserve_b, oracle_b_info, clock)?,
+        })
+    }
+
+    /// Price of the smallest denomination of a reserve's liquidity token
+    pub fn unit_price(
+        reserve: &Reserve,
+        oracle_info: &AccountInfo,
+        clock: &Clock,
+    ) -> Result<Decimal, ProgramError> {
+        if reserve.oracle != COption::Some(*oracle_info.key) {
+            msg!("Invalid reserve oracle account");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        let decimals = 10u64
+            .checked_pow(reserve.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        get_oracle_price(oracle_info, clock)?.try_div(decimals)
+    }
+
+    /// Amount of the other token which is worth one unit of the given token
+    fn relative_price(&self, token_mint: &Pubkey) -> Result<Decimal, ProgramError> {
+        if token_mint == self.token_mint_a {
+            self.unit_price_a.try_div(self.unit_price_b)
+        } else if token_mint == self.token_mint_b {
+            self.unit_price_b.try_div(self.unit_price_a)
+        } else {
+            Err(LendingError::InvalidTokenMint.into())
+        }
+    }
+}
+
+impl<'a> TokenConverter for OracleConverter<'a> {
+    fn best_price(&mut self, token_mint: &Pubkey) -> Result<Decimal, ProgramError> {
+        self.relative_price(token_mint)
+    }
+
+    fn convert(
+        self,
+        from_amount: Decimal,
+        from_token_mint: &Pubkey,
+    ) -> Result<Decimal, ProgramError> {
+        from_amount.try_mul(self.relative_price(from_token_mint)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrayref::{array_mut_ref, mut_array_refs};
+
+    fn pyth_price_data(price: i64, confidence: u64, exponent: i32, publish_slot: Slot) -> Vec<u8> {
+        let mut data = vec![0u8; PYTH_PRICE_LEN];
+        let output = array_mut_ref![data, 0, PYTH_PRICE_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (magic, version, account_type, _, _, expo, _, agg_price, agg_conf, status, _, pub_slot) =
+            mut_array_refs![output, 4, 4, 4, 4, 4, 4, 184, 8, 8, 4, 4, 8];
+        *magic = PYTH_MAGIC.to_le_bytes();
+        *version = PYTH_VERSION.to_le_bytes();
+        *account_type = PYTH_PRICE_ACCOUNT_TYPE.to_le_bytes();
+        *expo = exponent.to_le_bytes();
+        *agg_price = price.to_le_bytes();
+        *agg_conf = confidence.to_le_bytes();
+        *status = PYTH_STATUS_TRADING.to_le_bytes();
+        *pub_slot = publish_slot.to_le_bytes();
+        data
+    }
+
+    fn switchboard_price_data(
+        result: f64,
+        num_success: i32,
+        min_response: f64,
+        max_response: f64,
+        round_open_slot: Slot,
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; SWITCHBOARD_RESULT_LEN];
+        let output = array_mut_ref![data, 0, SWITCHBOARD_RESULT_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (account_type, _, success, _, round_result, open_slot, open_timestamp, min, max) =
+            mut_array_refs![output, 1, 32, 4, 4, 8, 8, 8, 8, 8];
+        account_type[0] = SWITCHBOARD_RESULT_ACCOUNT_TYPE;
+        *success = num_success.to_le_bytes();
+        *round_result = result.to_le_bytes();
+        *open_slot = round_open_slot.to_le_bytes();
+        *open_timestamp = 1_600_000_000i64.to_le_bytes();
+        *min = min_response.to_le_bytes();
+        *max = max_response.to_le_bytes();
+        data
+    }
+
+    fn clock(slot: Slot) -> Clock {
+        Clock {
+            slot,
+            ..Clock::default()
+        }
+    }
+
+    #[test]
+    fn pyth_price() {
+        let data = pyth_price_data(12_345_000, 1_000, -5, 10);
+        let oracle_price = unpack_pyth_price(&data).unwrap();
+        assert_eq!(
+            oracle_price.price,
+            Decimal::from(12_345_000u64).try_div(100_000).unwrap()
+        );
+        assert_eq!(oracle_price.validate(&clock(10)), Ok(oracle_price.price));
+        assert_eq!(
+            oracle_price.validate(&clock(11 + STALE_AFTER_SLOTS_ELAPSED)),
+            Err(LendingError::OraclePriceStale.into())
+        );
+    }
+
+    #[test]
+    fn pyth_price_uncertain() {
+        let data = pyth_price_data(100, 3, 0, 0);
+        let oracle_price = unpack_pyth_price(&data).unwrap();
+        assert_eq!(
+            oracle_price.validate(&clock(0)),
+            Err(LendingError::OraclePriceUncertain.into())
+        );
+    }
+
+    #[test]
+    fn pyth_price_invalid() {
+        let mut data = pyth_price_data(-1, 0, 0, 0);
+        assert_eq!(
+            unpack_pyth_price(&data),
+            Err(LendingError::InvalidOracleConfig.into())
+        );
+
+        data[0] = 0;
+        assert_eq!(
+            unpack_pyth_price(&data),
+            Err(LendingError::InvalidOracleConfig.into())
+        );
+    }
+
+    #[test]
+    fn switchboard_price() {
+        let data = switchboard_price_data(123.5, 3, 123.0, 124.0, 10);
+        let oracle_price = unpack_switchboard_price(&data).unwrap();
+        assert_eq!(
+            oracle_price,
+            OraclePrice {
+                price: Decimal::from(123_500_000_000u64)
+                    .try_div(SWITCHBOARD_PRICE_SCALE)
+                    .unwrap(),
+                confidence: Decimal::from(500_000_000u64)
+                    .try_div(SWITCHBOARD_PRICE_SCALE)
+                    .unwrap(),
+                publish_slot: 10,
+            }
+        );
+        assert_eq!(oracle_price.validate(&clock(10)), Ok(oracle_price.price));
+        assert_eq!(
+            oracle_price.validate(&clock(11 + STALE_AFTER_SLOTS_ELAPSED)),
+            Err(LendingError::OraclePriceStale.into())
+        );
+    }
+
+    #[test]
+    fn switchboard_price_invalid() {
+        let mut data = switchboard_price_data(1.0, 1, 1.0, 1.0, 0);
+        data[0] = SWITCHBOARD_RESULT_ACCOUNT_TYPE + 1;
+        assert_eq!(
+            unpack_switchboard_price(&data),
+            Err(LendingError::InvalidOracleConfig.into())
+        );
+
+        let data = switchboard_price_data(1.0, 0, 1.0, 1.0, 0);
+        assert_eq!(
+            unpack_switchboard_price(&data),
+            Err(LendingError::InvalidOracleConfig.into())
+        );
+
+        let data = switchboard_price_data(1.0, 1, 1.0, 1.0, 0);
+        assert_eq!(
+            unpack_switchboard_price(&data[..SWITCHBOARD_RESULT_LEN - 1]),
+            Err(LendingError::InvalidOracleConfig.into())
+        );
+    }
+}
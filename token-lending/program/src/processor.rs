@@ -4,10 +4,12 @@ use crate::{
     dex_market::{DexMarket, TradeSimulator, BASE_MINT_OFFSET, QUOTE_MINT_OFFSET},
     error::LendingError,
     instruction::{BorrowAmountType, LendingInstruction},
-    math::{Decimal, TryAdd, WAD},
+    math::{Decimal, Rate, TryAdd, WAD},
+    oracle::{get_oracle_price, OracleConverter},
     state::{
         LendingMarket, LiquidateResult, NewObligationParams, NewReserveParams, Obligation,
-        RepayResult, Reserve, ReserveCollateral, ReserveConfig, ReserveLiquidity, PROGRAM_VERSION,
+        RepayResult, Reserve, ReserveCollateral, ReserveConfig, ReserveLiquidity, TokenConverter,
+        PROGRAM_VERSION,
     },
 };
 use num_traits::FromPrimitive;
@@ -25,6 +27,7 @@ use solana_program::{
     sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 use spl_token::state::Account as Token;
+use std::slice::Iter;
 
 /// Processes an instruction
 pub fn process_instruction(
@@ -88,6 +91,18 @@ pub fn process_instruction(
             msg!("Instruction: Set Lending Market Owner");
             process_set_lending_market_owner(program_id, new_owner, accounts)
         }
+        LendingInstruction::RefreshObligation => {
+            msg!("Instruction: Refresh Obligation");
+            process_refresh_obligation(program_id, accounts)
+        }
+        LendingInstruction::RedeemFees => {
+            msg!("Instruction: Redeem Fees");
+            process_redeem_fees(program_id, accounts)
+        }
+        LendingInstruction::CloseObligation => {
+            msg!("Instruction: Close Obligation");
+            process_close_obligation(program_id, accounts)
+        }
     }
 }
 
@@ -151,12 +166,23 @@ fn process_init_reserve(
         msg!("Liquidation threshold must be in range (LTV, 100]");
         return Err(LendingError::InvalidConfig.into());
     }
-    if config.optimal_borrow_rate < config.min_borrow_rate {
-        msg!("Optimal borrow rate must be >= min borrow rate");
+    if config
+        .base_borrow_rate
+        .checked_add(config.borrow_rate_slope_1)
+        .and_then(|rate| rate.checked_add(config.borrow_rate_slope_2))
+        .is_none()
+    {
+        msg!("Borrow rate at full utilization must be in range [0, 255]");
         return Err(LendingError::InvalidConfig.into());
     }
-    if config.optimal_borrow_rate > config.max_borrow_rate {
-        msg!("Optimal borrow rate must be <= max borrow rate");
+    if config.min_liquidation_close_factor == 0 || config.min_liquidation_close_factor > 100 {
+        msg!("Min liquidation close factor must be in range (0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.max_liquidation_close_factor < config.min_liquidation_close_factor
+        || config.max_liquidation_close_factor > 100
+    {
+        msg!("Max liquidation close factor must be in range [min close factor, 100]");
         return Err(LendingError::InvalidConfig.into());
     }
     if config.fees.borrow_fee_wad >= WAD {
@@ -167,6 +193,10 @@ fn process_init_reserve(
         msg!("Host fee percentage must be in range [0, 100]");
         return Err(LendingError::InvalidConfig.into());
     }
+    if config.fees.protocol_take_rate > 100 {
+        msg!("Protocol take rate must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
 
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
@@ -177,6 +207,7 @@ fn process_init_reserve(
     let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
     let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
     let reserve_collateral_fees_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fees_receiver_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_owner_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
@@ -230,6 +261,13 @@ fn process_init_reserve(
         COption::None
     };
 
+    let oracle = if let Ok(oracle_info) = next_account_info(account_info_iter) {
+        get_oracle_price(oracle_info, clock)?;
+        COption::Some(*oracle_info.key)
+    } else {
+        COption::None
+    };
+
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
         &[lending_market.bump_seed],
@@ -249,6 +287,7 @@ fn process_init_reserve(
         *reserve_liquidity_mint_info.key,
         reserve_liquidity_mint.decimals,
         *reserve_liquidity_supply_info.key,
+        *reserve_liquidity_fees_receiver_info.key,
     );
     let reserve_collateral_info = ReserveCollateral::new(
         *reserve_collateral_mint_info.key,
@@ -261,6 +300,7 @@ fn process_init_reserve(
         collateral: reserve_collateral_info,
         liquidity: reserve_liquidity_info,
         dex_market,
+        oracle,
         config,
     });
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
@@ -274,6 +314,14 @@ fn process_init_reserve(
         token_program: token_program_id.clone(),
     })?;
 
+    spl_token_init_account(TokenInitializeAccountParams {
+        account: reserve_liquidity_fees_receiver_info.clone(),
+        mint: reserve_liquidity_mint_info.clone(),
+        owner: lending_market_authority_info.clone(),
+        rent: rent_info.clone(),
+        token_program: token_program_id.clone(),
+    })?;
+
     spl_token_init_mint(TokenInitializeMintParams {
         mint: reserve_collateral_mint_info.clone(),
         authority: lending_market_authority_info.key,
@@ -385,10 +433,12 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
     let cumulative_borrow_rate = borrow_reserve.cumulative_borrow_rate_wads;
     let obligation_mint_decimals = deposit_reserve.liquidity.mint_decimals;
     let obligation = Obligation::new(NewObligationParams {
+        current_slot: clock.slot,
         collateral_reserve: *deposit_reserve_info.key,
         cumulative_borrow_rate_wads: cumulative_borrow_rate,
         borrow_reserve: *borrow_reserve_info.key,
         token_mint: *obligation_token_mint_info.key,
+        owner: *obligation_token_owner_info.key,
     });
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
@@ -626,17 +676,9 @@ fn process_borrow(
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let dex_market_info = next_account_info(account_info_iter)?;
-    let dex_market_orders_info = next_account_info(account_info_iter)?;
-    let memory = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    // Ensure memory is owned by this program so that we don't have to zero it out
-    if memory.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -694,24 +736,6 @@ fn process_borrow(
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    // TODO: handle case when neither reserve is the quote currency
-    if borrow_reserve.dex_market.is_none() && deposit_reserve.dex_market.is_none() {
-        msg!("One reserve must have a dex market");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if let COption::Some(dex_market_pubkey) = borrow_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-    if let COption::Some(dex_market_pubkey) = deposit_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -741,29 +765,66 @@ fn process_borrow(
 
     assert_last_update_slot(&borrow_reserve, clock.slot)?;
     assert_last_update_slot(&deposit_reserve, clock.slot)?;
-    obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
+    assert_obligation_refreshed(&obligation, clock.slot)?;
 
-    let trade_simulator = TradeSimulator::new(
-        dex_market_info,
-        dex_market_orders_info,
-        memory,
-        &lending_market.quote_token_mint,
-        &borrow_reserve.liquidity.mint_pubkey,
-        &deposit_reserve.liquidity.mint_pubkey,
-    )?;
+    let loan_to_value_ratio = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
+    if obligation.loan_to_value()? > loan_to_value_ratio.into() {
+        return Err(LendingError::ObligationCollateralBelowRequired.into());
+    }
 
-    let loan = deposit_reserve.create_loan(
-        token_amount,
-        token_amount_type,
-        trade_simulator,
-        &borrow_reserve.liquidity.mint_pubkey,
-    )?;
+    // Reserves which both have an oracle are priced by their oracles instead of the dex market
+    let loan = if uses_oracle_prices(&deposit_reserve, &borrow_reserve)? {
+        let deposit_reserve_oracle_info = next_account_info(account_info_iter)?;
+        let borrow_reserve_oracle_info = next_account_info(account_info_iter)?;
+        let oracle_converter = OracleConverter::new(
+            &deposit_reserve,
+            deposit_reserve_oracle_info,
+            &borrow_reserve,
+            borrow_reserve_oracle_info,
+            clock,
+        )?;
+
+        deposit_reserve.create_loan(
+            token_amount,
+            token_amount_type,
+            oracle_converter,
+            &borrow_reserve.liquidity.mint_pubkey,
+        )?
+    } else {
+        let dex_market_info = next_account_info(account_info_iter)?;
+        let dex_market_orders_info = next_account_info(account_info_iter)?;
+        let memory = next_account_info(account_info_iter)?;
+        assert_dex_market_accounts(
+            program_id,
+            &deposit_reserve,
+            &borrow_reserve,
+            dex_market_info,
+            memory,
+        )?;
+
+        let trade_simulator = TradeSimulator::new(
+            dex_market_info,
+            dex_market_orders_info,
+            memory,
+            &lending_market.quote_token_mint,
+            &borrow_reserve.liquidity.mint_pubkey,
+            &deposit_reserve.liquidity.mint_pubkey,
+        )?;
+
+        deposit_reserve.create_loan(
+            token_amount,
+            token_amount_type,
+            trade_simulator,
+            &borrow_reserve.liquidity.mint_pubkey,
+        )?
+    };
 
-    borrow_reserve.liquidity.borrow(loan.borrow_amount)?;
+    borrow_reserve.borrow_liquidity(loan.borrow_amount)?;
     obligation.borrowed_liquidity_wads = obligation
         .borrowed_liquidity_wads
         .try_add(Decimal::from(loan.borrow_amount))?;
     obligation.deposited_collateral_tokens += loan.collateral_amount;
+    obligation.mark_stale();
 
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
     Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
@@ -945,6 +1006,7 @@ fn process_repay(
         collateral_withdraw_amount,
         obligation_token_amount,
     } = obligation.repay(liquidity_amount, obligation_mint.supply)?;
+    obligation.mark_stale();
     repay_reserve
         .liquidity
         .repay(integer_repay_amount, decimal_repay_amount)?;
@@ -1016,17 +1078,9 @@ fn process_liquidate(
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let dex_market_info = next_account_info(account_info_iter)?;
-    let dex_market_orders_info = next_account_info(account_info_iter)?;
-    let memory = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    // Ensure memory is owned by this program so that we don't have to zero it out
-    if memory.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -1091,53 +1145,21 @@ fn process_liquidate(
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    // TODO: handle case when neither reserve is the quote currency
-    if repay_reserve.dex_market.is_none() && withdraw_reserve.dex_market.is_none() {
-        msg!("One reserve must have a dex market");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if let COption::Some(dex_market_pubkey) = repay_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-    if let COption::Some(dex_market_pubkey) = withdraw_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-
-    // accrue interest and update rates
     assert_last_update_slot(&repay_reserve, clock.slot)?;
     assert_last_update_slot(&withdraw_reserve, clock.slot)?;
-    obligation.accrue_interest(repay_reserve.cumulative_borrow_rate_wads)?;
-
-    let trade_simulator = TradeSimulator::new(
-        dex_market_info,
-        dex_market_orders_info,
-        memory,
-        &lending_market.quote_token_mint,
-        &withdraw_reserve.liquidity.mint_pubkey,
-        &repay_reserve.liquidity.mint_pubkey,
-    )?;
+    assert_obligation_refreshed(&obligation, clock.slot)?;
 
     let LiquidateResult {
         withdraw_amount,
         repay_amount,
         settle_amount,
-    } = withdraw_reserve.liquidate_obligation(
-        &obligation,
-        liquidity_amount,
-        &repay_reserve.liquidity.mint_pubkey,
-        trade_simulator,
-    )?;
+    } = withdraw_reserve.liquidate_obligation(&obligation, liquidity_amount)?;
 
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
     Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
 
     obligation.liquidate(settle_amount, withdraw_amount)?;
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let authority_signer_seeds = &[
@@ -1267,6 +1289,7 @@ fn process_deposit_obligation_collateral(
         .deposited_collateral_tokens
         .checked_add(collateral_amount)
         .ok_or(LendingError::MathOverflow)?;
+    obligation.mark_stale();
 
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
@@ -1324,17 +1347,9 @@ fn process_withdraw_obligation_collateral(
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let dex_market_info = next_account_info(account_info_iter)?;
-    let dex_market_orders_info = next_account_info(account_info_iter)?;
-    let memory = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    // Ensure memory is owned by this program so that we don't have to zero it out
-    if memory.owner != program_id {
-        return Err(LendingError::InvalidAccountOwner.into());
-    }
-
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
@@ -1380,29 +1395,15 @@ fn process_withdraw_obligation_collateral(
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    // TODO: handle case when neither reserve is the quote currency
-    if borrow_reserve.dex_market.is_none() && withdraw_reserve.dex_market.is_none() {
-        msg!("One reserve must have a dex market");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if let COption::Some(dex_market_pubkey) = borrow_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-    if let COption::Some(dex_market_pubkey) = withdraw_reserve.dex_market {
-        if &dex_market_pubkey != dex_market_info.key {
-            msg!("Invalid dex market account input");
-            return Err(LendingError::InvalidAccountInput.into());
-        }
-    }
-
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
 
+    if &obligation.borrow_reserve != borrow_reserve_info.key {
+        msg!("Invalid borrow reserve account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
     if &obligation.collateral_reserve != withdraw_reserve_info.key {
         msg!("Invalid withdraw reserve account");
         return Err(LendingError::InvalidAccountInput.into());
@@ -1422,11 +1423,9 @@ fn process_withdraw_obligation_collateral(
         return Err(LendingError::InvalidTokenMint.into());
     }
 
-    // accrue interest and update rates
     assert_last_update_slot(&borrow_reserve, clock.slot)?;
     assert_last_update_slot(&withdraw_reserve, clock.slot)?;
-
-    obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
+    assert_obligation_refreshed(&obligation, clock.slot)?;
 
     let obligation_collateral_amount = obligation.deposited_collateral_tokens;
     if obligation_collateral_amount == 0 {
@@ -1436,20 +1435,9 @@ fn process_withdraw_obligation_collateral(
         return Err(LendingError::InvalidObligationCollateral.into());
     }
 
-    let trade_simulator = TradeSimulator::new(
-        dex_market_info,
-        dex_market_orders_info,
-        memory,
-        &lending_market.quote_token_mint,
-        &borrow_reserve.liquidity.mint_pubkey,
-        &withdraw_reserve.liquidity.mint_pubkey,
-    )?;
-
-    let required_collateral = withdraw_reserve.required_collateral_for_borrow(
-        obligation.borrowed_liquidity_wads.try_ceil_u64()?,
-        &borrow_reserve.liquidity.mint_pubkey,
-        trade_simulator,
-    )?;
+    let required_collateral = obligation.required_collateral(Rate::from_percent(
+        withdraw_reserve.config.loan_to_value_ratio,
+    ))?;
     if obligation_collateral_amount < required_collateral {
         return Err(LendingError::ObligationCollateralBelowRequired.into());
     }
@@ -1465,6 +1453,7 @@ fn process_withdraw_obligation_collateral(
         .collateral_to_obligation_token_amount(collateral_amount, obligation_token_mint.supply)?;
 
     obligation.deposited_collateral_tokens = remaining_collateral;
+    obligation.mark_stale();
 
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
@@ -1528,6 +1517,275 @@ fn process_set_lending_market_owner(
     Ok(())
 }
 
+#[inline(never)] // avoid stack frame limit
+fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.collateral_reserve != deposit_reserve_info.key {
+        msg!("Invalid deposit reserve account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.borrow_reserve != borrow_reserve_info.key {
+        msg!("Invalid borrow reserve account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    if deposit_reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &deposit_reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if borrow_reserve.lending_market != deposit_reserve.lending_market {
+        return Err(LendingError::LendingMarketMismatch.into());
+    }
+
+    assert_last_update_slot(&deposit_reserve, clock.slot)?;
+    assert_last_update_slot(&borrow_reserve, clock.slot)?;
+
+    let (collateral_token_price, borrow_token_price) = get_market_prices(
+        program_id,
+        &lending_market,
+        &deposit_reserve,
+        &borrow_reserve,
+        account_info_iter,
+        clock,
+    )?;
+
+    obligation.refresh(
+        borrow_reserve.cumulative_borrow_rate_wads,
+        deposit_reserve.collateral_exchange_rate()?,
+        collateral_token_price,
+        borrow_token_price,
+        clock.slot,
+    )?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_redeem_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fees_receiver_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Invalid reserve liquidity supply account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.fees_receiver != reserve_liquidity_fees_receiver_info.key {
+        msg!("Invalid reserve liquidity fees receiver account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let reserve_liquidity_fees_receiver =
+        Token::unpack(&reserve_liquidity_fees_receiver_info.data.borrow())?;
+    if reserve_liquidity_fees_receiver_info.owner != token_program_id.key {
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+
+    let destination_liquidity = Token::unpack(&destination_liquidity_info.data.borrow())?;
+    if destination_liquidity_info.owner != token_program_id.key {
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+    if destination_liquidity.owner != lending_market.owner {
+        msg!("Destination liquidity account must be owned by the lending market owner");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    assert_last_update_slot(&reserve, clock.slot)?;
+    let redeem_amount = reserve.redeem_fees()?;
+    let fees_amount = reserve_liquidity_fees_receiver
+        .amount
+        .checked_add(redeem_amount)
+        .ok_or(LendingError::MathOverflow)?;
+    if fees_amount == 0 {
+        msg!("No protocol fees available to redeem");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if lending_market_authority_info.key != &lending_market_authority_pubkey {
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    // credit protocol fees to the reserve fees receiver
+    if redeem_amount > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: reserve_liquidity_supply_info.clone(),
+            destination: reserve_liquidity_fees_receiver_info.clone(),
+            amount: redeem_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    // pay out all collected fees to the current lending market owner
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_liquidity_fees_receiver_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: fees_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_close_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_token_mint_info = next_account_info(account_info_iter)?;
+    let obligation_token_input_info = next_account_info(account_info_iter)?;
+    let obligation_token_owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.borrow_reserve != borrow_reserve_info.key {
+        msg!("Invalid borrow reserve account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.token_mint != obligation_token_mint_info.key {
+        msg!("Invalid obligation token mint account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    // obligations created before owners were recorded are closed by the holder of the entire
+    // obligation token supply
+    if obligation.owner != Pubkey::default() && &obligation.owner != obligation_token_owner_info.key
+    {
+        msg!("Invalid obligation owner account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation_info.key == destination_info.key {
+        msg!("Cannot use obligation as destination account input");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if obligation_token_mint_info.owner != &lending_market.token_program_id
+        || obligation_token_input_info.owner != &lending_market.token_program_id
+    {
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+    let obligation_mint = unpack_mint(&obligation_token_mint_info.data.borrow())?;
+    let obligation_token_input = Token::unpack(&obligation_token_input_info.data.borrow())?;
+    if &obligation_token_input.mint != obligation_token_mint_info.key {
+        msg!("Invalid obligation token input account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation_token_input.owner != obligation_token_owner_info.key {
+        msg!("Invalid obligation token owner account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if !obligation_token_owner_info.is_signer {
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation_token_input.amount != obligation_mint.supply {
+        msg!("Obligation token input must hold the entire obligation token supply");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    // accrue interest and write off any remaining dust, which lowers the borrow reserve's
+    // total liquidity and socializes the loss to its depositors
+    assert_last_update_slot(&borrow_reserve, clock.slot)?;
+    obligation.accrue_interest(borrow_reserve.cumulative_borrow_rate_wads)?;
+    let dust_amount = obligation.forgive_dust()?;
+    let settle_amount = dust_amount.min(borrow_reserve.liquidity.borrowed_amount_wads);
+    borrow_reserve.liquidity.repay(0, settle_amount)?;
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
+    // return rent and clear the obligation
+    let destination_starting_lamports = destination_info.lamports();
+    **destination_info.lamports.borrow_mut() = destination_starting_lamports
+        .checked_add(obligation_info.lamports())
+        .ok_or(LendingError::MathOverflow)?;
+    **obligation_info.lamports.borrow_mut() = 0;
+    for byte in obligation_info.data.borrow_mut().iter_mut() {
+        *byte = 0;
+    }
+
+    Ok(())
+}
+
 fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
     if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
         msg!(&rent.minimum_balance(account_info.data_len()).to_string());
@@ -1538,13 +1796,127 @@ fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult
 }
 
 fn assert_last_update_slot(reserve: &Reserve, slot: Slot) -> ProgramResult {
-    if !reserve.last_update_slot == slot {
+    if reserve.last_update_slot != slot {
         Err(LendingError::ReserveStale.into())
     } else {
         Ok(())
     }
 }
 
+fn assert_obligation_refreshed(obligation: &Obligation, slot: Slot) -> ProgramResult {
+    if obligation.stale || obligation.last_update_slot != slot {
+        Err(LendingError::ObligationStale.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether the reserves are priced by their oracles. Either both reserves or neither must have
+/// an oracle, so that a configured oracle is never silently replaced by dex market prices.
+fn uses_oracle_prices(
+    deposit_reserve: &Reserve,
+    borrow_reserve: &Reserve,
+) -> Result<bool, ProgramError> {
+    match (deposit_reserve.oracle, borrow_reserve.oracle) {
+        (COption::Some(_), COption::Some(_)) => Ok(true),
+        (COption::None, COption::None) => Ok(false),
+        _ => {
+            msg!("Deposit and borrow reserves must either both have an oracle or neither");
+            Err(LendingError::InvalidOracleConfig.into())
+        }
+    }
+}
+
+/// Price one unit of the deposit and borrow reserve liquidity in a common currency. Reserves
+/// which both have an oracle are priced by their oracle accounts, otherwise by the best order
+/// of the dex market shared with the quote currency reserve.
+fn get_market_prices(
+    program_id: &Pubkey,
+    lending_market: &LendingMarket,
+    deposit_reserve: &Reserve,
+    borrow_reserve: &Reserve,
+    account_info_iter: &mut Iter<AccountInfo>,
+    clock: &Clock,
+) -> Result<(Decimal, Decimal), ProgramError> {
+    if uses_oracle_prices(deposit_reserve, borrow_reserve)? {
+        let deposit_reserve_oracle_info = next_account_info(account_info_iter)?;
+        let borrow_reserve_oracle_info = next_account_info(account_info_iter)?;
+        return Ok((
+            OracleConverter::unit_price(deposit_reserve, deposit_reserve_oracle_info, clock)?,
+            OracleConverter::unit_price(borrow_reserve, borrow_reserve_oracle_info, clock)?,
+        ));
+    }
+
+    let dex_market_info = next_account_info(account_info_iter)?;
+    let dex_market_orders_info = next_account_info(account_info_iter)?;
+    let memory = next_account_info(account_info_iter)?;
+    assert_dex_market_accounts(
+        program_id,
+        deposit_reserve,
+        borrow_reserve,
+        dex_market_info,
+        memory,
+    )?;
+
+    let mut trade_simulator = TradeSimulator::new(
+        dex_market_info,
+        dex_market_orders_info,
+        memory,
+        &lending_market.quote_token_mint,
+        &borrow_reserve.liquidity.mint_pubkey,
+        &deposit_reserve.liquidity.mint_pubkey,
+    )?;
+
+    // Loans are valued at the best ask and collateral at the best bid
+    if deposit_reserve.liquidity.mint_pubkey == lending_market.quote_token_mint {
+        let borrow_token_price =
+            trade_simulator.best_price(&borrow_reserve.liquidity.mint_pubkey)?;
+        Ok((Decimal::one(), borrow_token_price))
+    } else if borrow_reserve.liquidity.mint_pubkey == lending_market.quote_token_mint {
+        let collateral_token_price =
+            trade_simulator.best_price(&deposit_reserve.liquidity.mint_pubkey)?;
+        Ok((collateral_token_price, Decimal::one()))
+    } else {
+        msg!("One reserve must be the quote currency");
+        Err(LendingError::InvalidAccountInput.into())
+    }
+}
+
+/// Check the dex market of the reserve which is not the quote currency and the program owned
+/// memory used to simulate trades
+fn assert_dex_market_accounts(
+    program_id: &Pubkey,
+    deposit_reserve: &Reserve,
+    borrow_reserve: &Reserve,
+    dex_market_info: &AccountInfo,
+    memory: &AccountInfo,
+) -> ProgramResult {
+    // Ensure memory is owned by this program so that we don't have to zero it out
+    if memory.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    // TODO: handle case when neither reserve is the quote currency
+    if borrow_reserve.dex_market.is_none() && deposit_reserve.dex_market.is_none() {
+        msg!("One reserve must have a dex market");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if let COption::Some(dex_market_pubkey) = borrow_reserve.dex_market {
+        if &dex_market_pubkey != dex_market_info.key {
+            msg!("Invalid dex market account input");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    }
+    if let COption::Some(dex_market_pubkey) = deposit_reserve.dex_market {
+        if &dex_market_pubkey != dex_market_info.key {
+            msg!("Invalid dex market account input");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    }
+
+    Ok(())
+}
+
 fn assert_uninitialized<T: Pack + IsInitialized>(
     account_info: &AccountInfo,
 ) -> Result<T, ProgramError> {
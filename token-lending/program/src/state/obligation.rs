@@ -5,6 +5,7 @@ use crate::{
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
+    clock::Slot,
     entrypoint::ProgramResult,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -17,6 +18,8 @@ use std::convert::TryInto;
 pub struct Obligation {
     /// Version of the obligation
     pub version: u8,
+    /// Last slot when interest accrued and the obligation was refreshed
+    pub last_update_slot: Slot,
     /// Amount of collateral tokens deposited for this obligation
     pub deposited_collateral_tokens: u64,
     /// Reserve which collateral tokens were deposited into
@@ -29,26 +32,41 @@ pub struct Obligation {
     pub borrow_reserve: Pubkey,
     /// Mint address of the tokens for this obligation
     pub token_mint: Pubkey,
+    /// Market value of the deposited collateral, as of the last refresh
+    pub collateral_value: Decimal,
+    /// Market value of the loan balance, as of the last refresh
+    pub borrowed_value: Decimal,
+    /// True when the collateral or loan balance changed since the last refresh
+    pub stale: bool,
+    /// Owner authority which can close the obligation
+    pub owner: Pubkey,
 }
 
 impl Obligation {
     /// Create new obligation
     pub fn new(params: NewObligationParams) -> Self {
         let NewObligationParams {
+            current_slot,
             collateral_reserve,
             borrow_reserve,
             token_mint,
             cumulative_borrow_rate_wads,
+            owner,
         } = params;
 
         Self {
             version: PROGRAM_VERSION,
+            last_update_slot: current_slot,
             deposited_collateral_tokens: 0,
             collateral_reserve,
             cumulative_borrow_rate_wads,
             borrowed_liquidity_wads: Decimal::zero(),
             borrow_reserve,
             token_mint,
+            collateral_value: Decimal::zero(),
+            borrowed_value: Decimal::zero(),
+            stale: false,
+            owner,
         }
     }
 
@@ -62,24 +80,56 @@ impl Obligation {
         }
     }
 
-    /// Maximum amount of loan that can be repaid by liquidators
-    pub fn max_liquidation_amount(&self) -> Result<u64, ProgramError> {
+    /// Whether the obligation has no collateral and at most a closeable loan balance left
+    pub fn is_empty(&self) -> bool {
+        self.deposited_collateral_tokens == 0
+            && self.borrowed_liquidity_wads < Decimal::from(CLOSEABLE_AMOUNT)
+    }
+
+    /// Write off the remaining loan balance of an empty obligation, returning the dust amount
+    pub fn forgive_dust(&mut self) -> Result<Decimal, ProgramError> {
+        if !self.is_empty() {
+            return Err(LendingError::ObligationNotEmpty.into());
+        }
+
+        let dust_amount = self.borrowed_liquidity_wads;
+        self.borrowed_liquidity_wads = Decimal::zero();
+        Ok(dust_amount)
+    }
+
+    /// Maximum amount of loan that can be repaid by liquidators for the given close factor
+    pub fn max_liquidation_amount(&self, close_factor: Rate) -> Result<u64, ProgramError> {
         self.borrowed_liquidity_wads
-            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
+            .try_mul(close_factor)?
             .try_floor_u64()
     }
 
-    /// Ratio of loan balance to collateral value
-    pub fn loan_to_value(
-        &self,
-        collateral_exchange_rate: CollateralExchangeRate,
-        borrow_token_price: Decimal,
-    ) -> Result<Decimal, ProgramError> {
-        let loan = self.borrowed_liquidity_wads;
-        let collateral_value = collateral_exchange_rate
-            .decimal_collateral_to_liquidity(self.deposited_collateral_tokens.into())?
-            .try_div(borrow_token_price)?;
-        loan.try_div(collateral_value)
+    /// Ratio of loan value to collateral value, as of the last refresh. A loan backed by
+    /// worthless collateral has the maximum ratio, so it is always unhealthy.
+    pub fn loan_to_value(&self) -> Result<Decimal, ProgramError> {
+        if self.borrowed_value == Decimal::zero() {
+            return Ok(Decimal::zero());
+        }
+        if self.collateral_value == Decimal::zero() {
+            return Ok(Decimal::from(u64::MAX));
+        }
+        self.borrowed_value.try_div(self.collateral_value)
+    }
+
+    /// Minimum amount of collateral tokens which keeps the loan within the given
+    /// loan to value ratio, as of the last refresh
+    pub fn required_collateral(&self, loan_to_value_ratio: Rate) -> Result<u64, ProgramError> {
+        if self.borrowed_value == Decimal::zero() {
+            return Ok(0);
+        }
+        if self.collateral_value == Decimal::zero() {
+            return Ok(self.deposited_collateral_tokens);
+        }
+        self.borrowed_value
+            .try_div(loan_to_value_ratio)?
+            .try_mul(self.deposited_collateral_tokens)?
+            .try_div(self.collateral_value)?
+            .try_ceil_u64()
     }
 
     /// Amount of obligation tokens for given collateral
@@ -113,6 +163,31 @@ impl Obligation {
         Ok(())
     }
 
+    /// Accrue interest up to the borrow reserve's cumulative rate, value the collateral and
+    /// loan at the given token prices, and mark the obligation as refreshed for the current slot
+    pub fn refresh(
+        &mut self,
+        cumulative_borrow_rate: Decimal,
+        collateral_exchange_rate: CollateralExchangeRate,
+        collateral_token_price: Decimal,
+        borrow_token_price: Decimal,
+        current_slot: Slot,
+    ) -> ProgramResult {
+        self.accrue_interest(cumulative_borrow_rate)?;
+        self.collateral_value = collateral_exchange_rate
+            .decimal_collateral_to_liquidity(self.deposited_collateral_tokens.into())?
+            .try_mul(collateral_token_price)?;
+        self.borrowed_value = self.borrowed_liquidity_wads.try_mul(borrow_token_price)?;
+        self.last_update_slot = current_slot;
+        self.stale = false;
+        Ok(())
+    }
+
+    /// Require a refresh before the stored market values are used again
+    pub fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
     /// Liquidate part of obligation
     pub fn liquidate(&mut self, repay_amount: Decimal, withdraw_amount: u64) -> ProgramResult {
         self.borrowed_liquidity_wads = self.borrowed_liquidity_wads.try_sub(repay_amount)?;
@@ -172,6 +247,8 @@ pub struct RepayResult {
 
 /// Create new obligation
 pub struct NewObligationParams {
+    /// Current slot
+    pub current_slot: Slot,
     /// Collateral reserve address
     pub collateral_reserve: Pubkey,
     /// Borrow reserve address
@@ -180,6 +257,8 @@ pub struct NewObligationParams {
     pub token_mint: Pubkey,
     /// Borrow rate used for calculating interest.
     pub cumulative_borrow_rate_wads: Decimal,
+    /// Owner authority which can close the obligation
+    pub owner: Pubkey,
 }
 
 impl Sealed for Obligation {}
@@ -205,8 +284,13 @@ impl Pack for Obligation {
             borrowed_liquidity_wads,
             borrow_reserve,
             token_mint,
+            last_update_slot,
+            collateral_value,
+            borrowed_value,
+            stale,
+            owner,
             _padding,
-        ) = array_refs![input, 1, 8, 32, 16, 16, 32, 32, 128];
+        ) = array_refs![input, 1, 8, 32, 16, 16, 32, 32, 8, 16, 16, 1, 32, 55];
         Ok(Self {
             version: u8::from_le_bytes(*version),
             deposited_collateral_tokens: u64::from_le_bytes(*deposited_collateral_tokens),
@@ -215,6 +299,11 @@ impl Pack for Obligation {
             borrowed_liquidity_wads: unpack_decimal(borrowed_liquidity_wads),
             borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
             token_mint: Pubkey::new_from_array(*token_mint),
+            last_update_slot: u64::from_le_bytes(*last_update_slot),
+            collateral_value: unpack_decimal(collateral_value),
+            borrowed_value: unpack_decimal(borrowed_value),
+            stale: stale[0] != 0,
+            owner: Pubkey::new_from_array(*owner),
         })
     }
 
@@ -228,8 +317,13 @@ impl Pack for Obligation {
             borrowed_liquidity_wads,
             borrow_reserve,
             token_mint,
+            last_update_slot,
+            collateral_value,
+            borrowed_value,
+            stale,
+            owner,
             _padding,
-        ) = mut_array_refs![output, 1, 8, 32, 16, 16, 32, 32, 128];
+        ) = mut_array_refs![output, 1, 8, 32, 16, 16, 32, 32, 8, 16, 16, 1, 32, 55];
 
         *version = self.version.to_le_bytes();
         *deposited_collateral_tokens = self.deposited_collateral_tokens.to_le_bytes();
@@ -238,6 +332,11 @@ impl Pack for Obligation {
         pack_decimal(self.borrowed_liquidity_wads, borrowed_liquidity_wads);
         borrow_reserve.copy_from_slice(self.borrow_reserve.as_ref());
         token_mint.copy_from_slice(self.token_mint.as_ref());
+        *last_update_slot = self.last_update_slot.to_le_bytes();
+        pack_decimal(self.collateral_value, collateral_value);
+        pack_decimal(self.borrowed_value, borrowed_value);
+        stale[0] = self.stale as u8;
+        owner.copy_from_slice(self.owner.as_ref());
     }
 }
 
@@ -280,6 +379,91 @@ mod test {
         );
     }
 
+    #[test]
+    fn obligation_refresh() {
+        let mut obligation = Obligation {
+            cumulative_borrow_rate_wads: Decimal::from(2u64),
+            ..Obligation::default()
+        };
+        // two liquidity tokens for each collateral token
+        let collateral_exchange_rate = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 200,
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 100,
+                ..ReserveCollateral::default()
+            },
+            ..Reserve::default()
+        }
+        .collateral_exchange_rate()
+        .unwrap();
+        assert_eq!(
+            obligation.refresh(
+                Decimal::one(),
+                collateral_exchange_rate,
+                Decimal::one(),
+                Decimal::one(),
+                10
+            ),
+            Err(LendingError::NegativeInterestRate.into())
+        );
+        assert_eq!(obligation.last_update_slot, 0);
+
+        obligation.deposited_collateral_tokens = 100;
+        obligation.borrowed_liquidity_wads = Decimal::from(10u64);
+        obligation.mark_stale();
+        assert_eq!(
+            obligation.refresh(
+                Decimal::from(2u64),
+                collateral_exchange_rate,
+                Decimal::from(3u64),
+                Decimal::from(4u64),
+                10
+            ),
+            Ok(())
+        );
+        assert_eq!(obligation.last_update_slot, 10);
+        assert!(!obligation.stale);
+        assert_eq!(obligation.collateral_value, Decimal::from(600u64));
+        assert_eq!(obligation.borrowed_value, Decimal::from(40u64));
+        assert_eq!(
+            obligation.loan_to_value(),
+            Ok(Decimal::from(40u64).try_div(600u64).unwrap())
+        );
+        assert_eq!(
+            obligation.required_collateral(Rate::from_percent(50)),
+            Ok(14)
+        );
+    }
+
+    #[test]
+    fn obligation_worthless_collateral() {
+        let obligation = Obligation {
+            deposited_collateral_tokens: 100,
+            borrowed_liquidity_wads: Decimal::from(10u64),
+            collateral_value: Decimal::zero(),
+            borrowed_value: Decimal::from(10u64),
+            ..Obligation::default()
+        };
+        assert_eq!(obligation.loan_to_value(), Ok(Decimal::from(u64::MAX)));
+        assert_eq!(
+            obligation.required_collateral(Rate::from_percent(50)),
+            Ok(100)
+        );
+
+        let repaid_obligation = Obligation {
+            borrowed_value: Decimal::zero(),
+            ..obligation
+        };
+        assert_eq!(repaid_obligation.loan_to_value(), Ok(Decimal::zero()));
+        assert_eq!(
+            repaid_obligation.required_collateral(Rate::from_percent(50)),
+            Ok(0)
+        );
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
@@ -361,6 +545,23 @@ mod test {
             assert_eq!(state.deposited_collateral_tokens, 0);
         }
 
+        #[test]
+        fn forgive_dust(
+            deposited_collateral_tokens in 0..=1u64,
+            borrowed_liquidity in 0..=(2 * WAD as u128 * CLOSEABLE_AMOUNT as u128),
+        ) {
+            let borrowed_liquidity_wads = Decimal::from_scaled_val(borrowed_liquidity);
+            let mut state = Obligation { deposited_collateral_tokens, borrowed_liquidity_wads, ..Obligation::default() };
+
+            if deposited_collateral_tokens == 0 && borrowed_liquidity_wads < Decimal::from(CLOSEABLE_AMOUNT) {
+                assert_eq!(state.forgive_dust()?, borrowed_liquidity_wads);
+                assert_eq!(state.borrowed_liquidity_wads, Decimal::zero());
+            } else {
+                assert_eq!(state.forgive_dust(), Err(LendingError::ObligationNotEmpty.into()));
+                assert_eq!(state.borrowed_liquidity_wads, borrowed_liquidity_wads);
+            }
+        }
+
         #[test]
         fn accrue_interest(
             borrowed_liquidity in 0..=u64::MAX,
@@ -2,7 +2,7 @@ use super::*;
 use crate::{
     error::LendingError,
     instruction::BorrowAmountType,
-    math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub},
+    math::{compound_rate, Decimal, KinkedRateModel, Rate, TryAdd, TryDiv, TryMul, TrySub},
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
@@ -15,12 +15,14 @@ use solana_program::{
 };
 use std::convert::{TryFrom, TryInto};
 
-/// Percentage of an obligation that can be repaid during each liquidation call
-pub const LIQUIDATION_CLOSE_FACTOR: u8 = 50;
-
-/// Loan amount that is small enough to close out
+/// Loan amount that is small enough to close out. Liquidators can repay a smaller balance in
+/// full, and CloseObligation writes it off as dust once the collateral is gone.
 pub const CLOSEABLE_AMOUNT: u64 = 2;
 
+/// Percentage of an obligation that can be repaid during each liquidation call, used by reserves
+/// which have no liquidation close factors configured
+pub const LIQUIDATION_CLOSE_FACTOR: u8 = 50;
+
 /// Lending market reserve state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Reserve {
@@ -34,6 +36,8 @@ pub struct Reserve {
     pub lending_market: Pubkey,
     /// Dex market state account
     pub dex_market: COption<Pubkey>,
+    /// Price oracle account
+    pub oracle: COption<Pubkey>,
     /// Reserve liquidity info
     pub liquidity: ReserveLiquidity,
     /// Reserve collateral info
@@ -51,6 +55,7 @@ impl Reserve {
             collateral: collateral_info,
             liquidity: liquidity_info,
             dex_market,
+            oracle,
             config,
         } = params;
 
@@ -62,6 +67,7 @@ impl Reserve {
             collateral: collateral_info,
             liquidity: liquidity_info,
             dex_market,
+            oracle,
             config,
         }
     }
@@ -69,27 +75,7 @@ impl Reserve {
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
-        let optimal_utilization_rate = Rate::from_percent(self.config.optimal_utilization_rate);
-        let low_utilization = utilization_rate < optimal_utilization_rate;
-        if low_utilization || self.config.optimal_utilization_rate == 100 {
-            let normalized_rate = utilization_rate.try_div(optimal_utilization_rate)?;
-            let min_rate = Rate::from_percent(self.config.min_borrow_rate);
-            let rate_range =
-                Rate::from_percent(self.config.optimal_borrow_rate - self.config.min_borrow_rate);
-
-            Ok(normalized_rate.try_mul(rate_range)?.try_add(min_rate)?)
-        } else {
-            let normalized_rate = utilization_rate
-                .try_sub(optimal_utilization_rate)?
-                .try_div(Rate::from_percent(
-                    100 - self.config.optimal_utilization_rate,
-                ))?;
-            let min_rate = Rate::from_percent(self.config.optimal_borrow_rate);
-            let rate_range =
-                Rate::from_percent(self.config.max_borrow_rate - self.config.optimal_borrow_rate);
-
-            Ok(normalized_rate.try_mul(rate_range)?.try_add(min_rate)?)
-        }
+        self.config.rate_model().borrow_rate(utilization_rate)
     }
 
     /// Liquidate part of an unhealthy obligation
@@ -97,33 +83,19 @@ impl Reserve {
         &self,
         obligation: &Obligation,
         liquidate_amount: u64,
-        liquidity_token_mint: &Pubkey,
-        token_converter: impl TokenConverter,
     ) -> Result<LiquidateResult, ProgramError> {
-        Self::_liquidate_obligation(
-            obligation,
-            liquidate_amount,
-            liquidity_token_mint,
-            self.collateral_exchange_rate()?,
-            &self.config,
-            token_converter,
-        )
+        Self::_liquidate_obligation(obligation, liquidate_amount, &self.config)
     }
 
     fn _liquidate_obligation(
         obligation: &Obligation,
         liquidity_amount: u64,
-        liquidity_token_mint: &Pubkey,
-        collateral_exchange_rate: CollateralExchangeRate,
         collateral_reserve_config: &ReserveConfig,
-        mut token_converter: impl TokenConverter,
     ) -> Result<LiquidateResult, ProgramError> {
         // Check obligation health
-        let borrow_token_price = token_converter.best_price(liquidity_token_mint)?;
         let liquidation_threshold =
             Rate::from_percent(collateral_reserve_config.liquidation_threshold);
-        let obligation_loan_to_value =
-            obligation.loan_to_value(collateral_exchange_rate, borrow_token_price)?;
+        let obligation_loan_to_value = obligation.loan_to_value()?;
         if obligation_loan_to_value < liquidation_threshold.into() {
             return Err(LendingError::HealthyObligation.into());
         }
@@ -140,16 +112,19 @@ impl Reserve {
         }
 
         // Calculate the amount of liquidity that will be repaid
-        let max_liquidation_amount = obligation.max_liquidation_amount()?;
+        let close_factor =
+            collateral_reserve_config.liquidation_close_factor(obligation_loan_to_value)?;
+        let max_liquidation_amount = obligation.max_liquidation_amount(close_factor)?;
         let repay_amount = liquidity_amount.min(max_liquidation_amount);
         let decimal_repay_amount = Decimal::from(repay_amount);
 
-        // Calculate the amount of collateral that will be received
+        // Calculate the amount of collateral that will be received, valued at the market
+        // prices from the last obligation refresh
         let withdraw_amount = {
-            let receive_liquidity_amount =
-                token_converter.convert(decimal_repay_amount, liquidity_token_mint)?;
-            let collateral_amount = collateral_exchange_rate
-                .decimal_liquidity_to_collateral(receive_liquidity_amount)?;
+            let collateral_amount = decimal_repay_amount
+                .try_div(obligation.borrowed_liquidity_wads)?
+                .try_mul(obligation_loan_to_value)?
+                .try_mul(obligation.deposited_collateral_tokens)?;
             let bonus_rate = Rate::from_percent(collateral_reserve_config.liquidation_bonus);
             let bonus_amount = collateral_amount.try_mul(bonus_rate)?;
             let withdraw_amount = collateral_amount.try_add(bonus_amount)?;
@@ -266,6 +241,16 @@ impl Reserve {
 
     /// Record deposited liquidity and return amount of collateral tokens to mint
     pub fn deposit_liquidity(&mut self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        if self.config.deposit_limit > 0 {
+            let total_supply = self
+                .liquidity
+                .total_supply()?
+                .try_add(Decimal::from(liquidity_amount))?;
+            if total_supply > Decimal::from(self.config.deposit_limit) {
+                return Err(LendingError::ReserveDepositLimitExceeded.into());
+            }
+        }
+
         let collateral_exchange_rate = self.collateral_exchange_rate()?;
         let collateral_amount =
             collateral_exchange_rate.liquidity_to_collateral(liquidity_amount)?;
@@ -276,6 +261,21 @@ impl Reserve {
         Ok(collateral_amount)
     }
 
+    /// Record borrowed liquidity, respecting the reserve borrow limit
+    pub fn borrow_liquidity(&mut self, borrow_amount: u64) -> ProgramResult {
+        if self.config.borrow_limit > 0 {
+            let borrowed_amount_wads = self
+                .liquidity
+                .borrowed_amount_wads
+                .try_add(Decimal::from(borrow_amount))?;
+            if borrowed_amount_wads > Decimal::from(self.config.borrow_limit) {
+                return Err(LendingError::ReserveBorrowLimitExceeded.into());
+            }
+        }
+
+        self.liquidity.borrow(borrow_amount)
+    }
+
     /// Record redeemed collateral and return amount of liquidity to withdraw
     pub fn redeem_collateral(&mut self, collateral_amount: u64) -> Result<u64, ProgramError> {
         let collateral_exchange_rate = self.collateral_exchange_rate()?;
@@ -291,6 +291,24 @@ impl Reserve {
         Ok(liquidity_amount)
     }
 
+    /// Record redeemed protocol fees and return amount of liquidity to transfer to the fee
+    /// receiver. Fees can only be redeemed up to the available liquidity.
+    pub fn redeem_fees(&mut self) -> Result<u64, ProgramError> {
+        let redeem_amount = self
+            .liquidity
+            .accumulated_protocol_fees_wads
+            .try_floor_u64()?
+            .min(self.liquidity.available_amount);
+
+        self.liquidity.available_amount -= redeem_amount;
+        self.liquidity.accumulated_protocol_fees_wads = self
+            .liquidity
+            .accumulated_protocol_fees_wads
+            .try_sub(Decimal::from(redeem_amount))?;
+
+        Ok(redeem_amount)
+    }
+
     /// Update borrow rate and accrue interest
     pub fn accrue_interest(&mut self, current_slot: Slot) -> ProgramResult {
         let slots_elapsed = self.update_slot(current_slot);
@@ -298,10 +316,19 @@ impl Reserve {
             let current_borrow_rate = self.current_borrow_rate()?;
             let compounded_interest_rate =
                 self.compound_interest(current_borrow_rate, slots_elapsed)?;
-            self.liquidity.borrowed_amount_wads = self
+            let previous_borrowed_amount_wads = self.liquidity.borrowed_amount_wads;
+            self.liquidity.borrowed_amount_wads =
+                previous_borrowed_amount_wads.try_mul(compounded_interest_rate)?;
+
+            let protocol_fees = self
                 .liquidity
                 .borrowed_amount_wads
-                .try_mul(compounded_interest_rate)?;
+                .try_sub(previous_borrowed_amount_wads)?
+                .try_mul(Rate::from_percent(self.config.fees.protocol_take_rate))?;
+            self.liquidity.accumulated_protocol_fees_wads = self
+                .liquidity
+                .accumulated_protocol_fees_wads
+                .try_add(protocol_fees)?;
         }
         Ok(())
     }
@@ -325,10 +352,8 @@ impl Reserve {
         current_borrow_rate: Rate,
         slots_elapsed: u64,
     ) -> Result<Rate, ProgramError> {
-        let slot_interest_rate: Rate = current_borrow_rate.try_div(SLOTS_PER_YEAR)?;
-        let compounded_interest_rate = Rate::one()
-            .try_add(slot_interest_rate)?
-            .try_pow(slots_elapsed)?;
+        let compounded_interest_rate =
+            compound_rate(current_borrow_rate, SLOTS_PER_YEAR, slots_elapsed)?;
         self.cumulative_borrow_rate_wads = self
             .cumulative_borrow_rate_wads
             .try_mul(compounded_interest_rate)?;
@@ -348,6 +373,8 @@ pub struct NewReserveParams {
     pub liquidity: ReserveLiquidity,
     /// Optional dex market address
     pub dex_market: COption<Pubkey>,
+    /// Optional price oracle address
+    pub oracle: COption<Pubkey>,
     /// Reserve configuration values
     pub config: ReserveConfig,
 }
@@ -385,27 +412,41 @@ pub struct ReserveLiquidity {
     pub mint_decimals: u8,
     /// Reserve liquidity supply address
     pub supply_pubkey: Pubkey,
+    /// Reserve liquidity protocol fees receiver address
+    pub fees_receiver: Pubkey,
     /// Reserve liquidity available
     pub available_amount: u64,
     /// Reserve liquidity borrowed
     pub borrowed_amount_wads: Decimal,
+    /// Protocol share of accrued interest which has not been redeemed yet
+    pub accumulated_protocol_fees_wads: Decimal,
 }
 
 impl ReserveLiquidity {
     /// New reserve liquidity info
-    pub fn new(mint_pubkey: Pubkey, mint_decimals: u8, supply_pubkey: Pubkey) -> Self {
+    pub fn new(
+        mint_pubkey: Pubkey,
+        mint_decimals: u8,
+        supply_pubkey: Pubkey,
+        fees_receiver: Pubkey,
+    ) -> Self {
         Self {
             mint_pubkey,
             mint_decimals,
             supply_pubkey,
+            fees_receiver,
             available_amount: 0,
             borrowed_amount_wads: Decimal::zero(),
+            accumulated_protocol_fees_wads: Decimal::zero(),
         }
     }
 
-    /// Calculate the total reserve supply including active loans
+    /// Calculate the total reserve supply including active loans, excluding protocol fees
     pub fn total_supply(&self) -> Result<Decimal, ProgramError> {
-        Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)
+        let total_supply =
+            Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)?;
+        // defaulted loans can leave unredeemed fees larger than the remaining supply
+        total_supply.try_sub(self.accumulated_protocol_fees_wads.min(total_supply))
     }
 
     /// Add new borrow amount to total borrows
@@ -434,7 +475,9 @@ impl ReserveLiquidity {
 
     /// Calculate the liquidity utilization rate of the reserve
     pub fn utilization_rate(&self) -> Result<Rate, ProgramError> {
-        let total_supply = self.total_supply()?;
+        // unredeemed protocol fees are still part of the liquidity being lent out
+        let total_supply =
+            Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)?;
         if total_supply == Decimal::zero() {
             return Ok(Rate::zero());
         }
@@ -533,21 +576,80 @@ pub struct ReserveConfig {
     pub liquidation_bonus: u8,
     /// The percent at which an obligation is considered unhealthy
     pub liquidation_threshold: u8,
-    /// Min borrow APY
-    pub min_borrow_rate: u8,
-    /// Optimal (utilization) borrow APY
-    pub optimal_borrow_rate: u8,
-    /// Max borrow APY
-    pub max_borrow_rate: u8,
+    /// Percent of an obligation's loan that can be repaid in one liquidation when the obligation
+    /// is at the liquidation threshold
+    pub min_liquidation_close_factor: u8,
+    /// Percent of an obligation's loan that can be repaid in one liquidation when the loan value
+    /// reaches the collateral value
+    pub max_liquidation_close_factor: u8,
+    /// Borrow APY at zero utilization
+    pub base_borrow_rate: u8,
+    /// Borrow APY increase from zero to optimal utilization
+    pub borrow_rate_slope_1: u8,
+    /// Borrow APY increase from optimal to full utilization. The rates at zero, optimal and full
+    /// utilization are stored, so their sum must not exceed 255%.
+    pub borrow_rate_slope_2: u8,
+    /// Maximum amount of liquidity in the reserve, including borrowed liquidity. Zero means no
+    /// limit, which is also how reserves created before limits existed unpack.
+    pub deposit_limit: u64,
+    /// Maximum amount of liquidity which can be borrowed from the reserve. Zero means no limit.
+    pub borrow_limit: u64,
     /// Program owner fees assessed, separate from gains due to interest accrual
     pub fees: ReserveFees,
 }
 
+impl ReserveConfig {
+    /// Kinked borrow rate model for this reserve
+    pub fn rate_model(&self) -> KinkedRateModel {
+        KinkedRateModel {
+            base_rate: Rate::from_percent(self.base_borrow_rate),
+            optimal_utilization_rate: Rate::from_percent(self.optimal_utilization_rate),
+            slope_1: Rate::from_percent(self.borrow_rate_slope_1),
+            slope_2: Rate::from_percent(self.borrow_rate_slope_2),
+        }
+    }
+
+    /// Percentage of an unhealthy obligation's loan that can be repaid in one liquidation.
+    ///
+    /// Scales linearly from the min close factor at the liquidation threshold to the max close
+    /// factor as the loan to value ratio approaches 100%. Reserves created before close factors
+    /// were configurable have neither set and use a fixed `LIQUIDATION_CLOSE_FACTOR`.
+    pub fn liquidation_close_factor(&self, loan_to_value: Decimal) -> Result<Rate, ProgramError> {
+        if self.min_liquidation_close_factor == 0 && self.max_liquidation_close_factor == 0 {
+            return Ok(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR));
+        }
+
+        let min_close_factor = Rate::from_percent(self.min_liquidation_close_factor);
+        let liquidation_threshold = Decimal::from_percent(self.liquidation_threshold);
+        if loan_to_value <= liquidation_threshold {
+            return Ok(min_close_factor);
+        }
+        if loan_to_value >= Decimal::one() {
+            return Ok(Rate::from_percent(self.max_liquidation_close_factor));
+        }
+
+        let normalized_ltv = Rate::try_from(
+            loan_to_value
+                .try_sub(liquidation_threshold)?
+                .try_div(Decimal::one().try_sub(liquidation_threshold)?)?,
+        )?;
+        let close_factor_range = Rate::from_percent(
+            self.max_liquidation_close_factor
+                .saturating_sub(self.min_liquidation_close_factor),
+        );
+
+        Ok(normalized_ltv
+            .try_mul(close_factor_range)?
+            .try_add(min_close_factor)?)
+    }
+}
+
 /// Additional fee information on a reserve
 ///
-/// These exist separately from interest accrual fees, and are specifically for
-/// the program owner and frontend host.  The fees are paid out as a percentage
-/// of collateral token amounts during repayments and liquidations.
+/// These are specifically for the program owner and frontend host. Borrow fees
+/// are paid out as a percentage of collateral token amounts, while the protocol
+/// take rate keeps a share of accrued interest in the reserve liquidity until it
+/// is redeemed by the lending market owner.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct ReserveFees {
     /// Fee assessed on `BorrowReserveLiquidity`, expressed as a Wad.
@@ -559,6 +661,8 @@ pub struct ReserveFees {
     pub borrow_fee_wad: u64,
     /// Amount of fee going to host account, if provided in liquidate and repay
     pub host_fee_percentage: u8,
+    /// Percentage of accrued interest kept by the program owner
+    pub protocol_take_rate: u8,
 }
 
 impl ReserveFees {
@@ -638,23 +742,40 @@ impl Pack for Reserve {
             total_borrows,
             available_liquidity,
             collateral_mint_supply,
+            oracle,
+            deposit_limit,
+            borrow_limit,
+            min_liquidation_close_factor,
+            max_liquidation_close_factor,
+            liquidity_fees_receiver,
+            protocol_take_rate,
+            accumulated_protocol_fees,
             __padding,
         ) = array_refs![
             input, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8,
-            300
+            36, 8, 8, 1, 1, 32, 1, 16, 197
         ];
+
+        // borrow rates are stored as the rates at zero, optimal and full utilization
+        let min_borrow_rate = u8::from_le_bytes(*min_borrow_rate);
+        let optimal_borrow_rate = u8::from_le_bytes(*optimal_borrow_rate);
+        let max_borrow_rate = u8::from_le_bytes(*max_borrow_rate);
+
         Ok(Self {
             version: u8::from_le_bytes(*version),
             last_update_slot: u64::from_le_bytes(*last_update_slot),
             cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate),
             lending_market: Pubkey::new_from_array(*lending_market),
             dex_market: unpack_coption_key(dex_market)?,
+            oracle: unpack_coption_key(oracle)?,
             liquidity: ReserveLiquidity {
                 mint_pubkey: Pubkey::new_from_array(*liquidity_mint),
                 mint_decimals: u8::from_le_bytes(*liquidity_mint_decimals),
                 supply_pubkey: Pubkey::new_from_array(*liquidity_supply),
+                fees_receiver: Pubkey::new_from_array(*liquidity_fees_receiver),
                 available_amount: u64::from_le_bytes(*available_liquidity),
                 borrowed_amount_wads: unpack_decimal(total_borrows),
+                accumulated_protocol_fees_wads: unpack_decimal(accumulated_protocol_fees),
             },
             collateral: ReserveCollateral {
                 mint_pubkey: Pubkey::new_from_array(*collateral_mint),
@@ -667,12 +788,17 @@ impl Pack for Reserve {
                 loan_to_value_ratio: u8::from_le_bytes(*loan_to_value_ratio),
                 liquidation_bonus: u8::from_le_bytes(*liquidation_bonus),
                 liquidation_threshold: u8::from_le_bytes(*liquidation_threshold),
-                min_borrow_rate: u8::from_le_bytes(*min_borrow_rate),
-                optimal_borrow_rate: u8::from_le_bytes(*optimal_borrow_rate),
-                max_borrow_rate: u8::from_le_bytes(*max_borrow_rate),
+                min_liquidation_close_factor: u8::from_le_bytes(*min_liquidation_close_factor),
+                max_liquidation_close_factor: u8::from_le_bytes(*max_liquidation_close_factor),
+                base_borrow_rate: min_borrow_rate,
+                borrow_rate_slope_1: optimal_borrow_rate.saturating_sub(min_borrow_rate),
+                borrow_rate_slope_2: max_borrow_rate.saturating_sub(optimal_borrow_rate),
+                deposit_limit: u64::from_le_bytes(*deposit_limit),
+                borrow_limit: u64::from_le_bytes(*borrow_limit),
                 fees: ReserveFees {
                     borrow_fee_wad: u64::from_le_bytes(*borrow_fee_wad),
                     host_fee_percentage: u8::from_le_bytes(*host_fee_percentage),
+                    protocol_take_rate: u8::from_le_bytes(*protocol_take_rate),
                 },
             },
         })
@@ -704,23 +830,37 @@ impl Pack for Reserve {
             total_borrows,
             available_liquidity,
             collateral_mint_supply,
+            oracle,
+            deposit_limit,
+            borrow_limit,
+            min_liquidation_close_factor,
+            max_liquidation_close_factor,
+            liquidity_fees_receiver,
+            protocol_take_rate,
+            accumulated_protocol_fees,
             _padding,
         ) = mut_array_refs![
             output, 1, 8, 32, 32, 1, 32, 32, 32, 32, 36, 1, 1, 1, 1, 1, 1, 1, 8, 1, 16, 16, 8, 8,
-            300
+            36, 8, 8, 1, 1, 32, 1, 16, 197
         ];
         *version = self.version.to_le_bytes();
         *last_update_slot = self.last_update_slot.to_le_bytes();
         pack_decimal(self.cumulative_borrow_rate_wads, cumulative_borrow_rate);
         lending_market.copy_from_slice(self.lending_market.as_ref());
         pack_coption_key(&self.dex_market, dex_market);
+        pack_coption_key(&self.oracle, oracle);
 
         // liquidity info
         liquidity_mint.copy_from_slice(self.liquidity.mint_pubkey.as_ref());
         *liquidity_mint_decimals = self.liquidity.mint_decimals.to_le_bytes();
         liquidity_supply.copy_from_slice(self.liquidity.supply_pubkey.as_ref());
+        liquidity_fees_receiver.copy_from_slice(self.liquidity.fees_receiver.as_ref());
         *available_liquidity = self.liquidity.available_amount.to_le_bytes();
         pack_decimal(self.liquidity.borrowed_amount_wads, total_borrows);
+        pack_decimal(
+            self.liquidity.accumulated_protocol_fees_wads,
+            accumulated_protocol_fees,
+        );
 
         // collateral info
         collateral_mint.copy_from_slice(self.collateral.mint_pubkey.as_ref());
@@ -733,11 +873,22 @@ impl Pack for Reserve {
         *loan_to_value_ratio = self.config.loan_to_value_ratio.to_le_bytes();
         *liquidation_bonus = self.config.liquidation_bonus.to_le_bytes();
         *liquidation_threshold = self.config.liquidation_threshold.to_le_bytes();
-        *min_borrow_rate = self.config.min_borrow_rate.to_le_bytes();
-        *optimal_borrow_rate = self.config.optimal_borrow_rate.to_le_bytes();
-        *max_borrow_rate = self.config.max_borrow_rate.to_le_bytes();
+        *min_liquidation_close_factor = self.config.min_liquidation_close_factor.to_le_bytes();
+        *max_liquidation_close_factor = self.config.max_liquidation_close_factor.to_le_bytes();
+        let optimal_rate = self
+            .config
+            .base_borrow_rate
+            .saturating_add(self.config.borrow_rate_slope_1);
+        *min_borrow_rate = self.config.base_borrow_rate.to_le_bytes();
+        *optimal_borrow_rate = optimal_rate.to_le_bytes();
+        *max_borrow_rate = optimal_rate
+            .saturating_add(self.config.borrow_rate_slope_2)
+            .to_le_bytes();
+        *deposit_limit = self.config.deposit_limit.to_le_bytes();
+        *borrow_limit = self.config.borrow_limit.to_le_bytes();
         *borrow_fee_wad = self.config.fees.borrow_fee_wad.to_le_bytes();
         *host_fee_percentage = self.config.fees.host_fee_percentage.to_le_bytes();
+        *protocol_take_rate = self.config.fees.protocol_take_rate.to_le_bytes();
     }
 }
 
@@ -765,25 +916,23 @@ mod test {
         }
     }
 
-    /// Convert reserve liquidity tokens to the collateral tokens of another reserve
-    fn liquidity_in_other_collateral(
-        liquidity_amount: u64,
-        collateral_exchange_rate: CollateralExchangeRate,
-        conversion_rate: Decimal,
-    ) -> Result<Decimal, ProgramError> {
-        collateral_exchange_rate.decimal_liquidity_to_collateral(
-            Decimal::from(liquidity_amount).try_mul(conversion_rate)?,
-        )
+    // Creates close factors (min, max) where 0 < min <= max <= 100
+    prop_compose! {
+        fn close_factors()(min_close_factor in 1..=100u8)(
+            min_close_factor in Just(min_close_factor),
+            max_close_factor in min_close_factor..=100u8,
+        ) -> (u8, u8) {
+            (min_close_factor, max_close_factor)
+        }
     }
 
-    // Creates rates (min, opt, max) where 0 <= min <= opt <= max <= MAX
+    // Creates amounts (limit, total) where total is within 2 of limit
     prop_compose! {
-        fn borrow_rates()(optimal_rate in 0..=u8::MAX)(
-            min_rate in 0..=optimal_rate,
-            optimal_rate in Just(optimal_rate),
-            max_rate in optimal_rate..=u8::MAX,
-        ) -> (u8, u8, u8) {
-            (min_rate, optimal_rate, max_rate)
+        fn limit_boundary_amounts()(limit in 0..=MAX_LIQUIDITY)(
+            limit in Just(limit),
+            total in limit.saturating_sub(2)..=limit.saturating_add(2),
+        ) -> (u64, u64) {
+            (limit, total)
         }
     }
 
@@ -823,63 +972,53 @@ mod test {
         #[test]
         fn unhealthy_obligations_can_be_liquidated(
             obligation_collateral in 1..=u64::MAX,
+            borrowed_liquidity in 2..=u64::MAX, // avoid dust account closure
             (obligation_ltv, liquidation_threshold) in unhealthy_rates(),
-            collateral_exchange_rate in collateral_exchange_rate_range(),
-            token_conversion_rate in token_conversion_rate(),
+            (min_liquidation_close_factor, max_liquidation_close_factor) in close_factors(),
+            collateral_token_price in token_conversion_rate(),
         ) {
             let collateral_reserve_config = &ReserveConfig {
                 liquidation_threshold,
+                min_liquidation_close_factor,
+                max_liquidation_close_factor,
                 ..ReserveConfig::default()
             };
+            let max_close_factor = Rate::from_percent(max_liquidation_close_factor);
 
             // Create unhealthy obligation at target LTV
-            let collateral_value = collateral_exchange_rate
-                .decimal_collateral_to_liquidity(Decimal::from(obligation_collateral as u64))?
-                .try_div(token_conversion_rate)?;
-
-            // Ensure that collateral value fits in u64
-            prop_assume!(collateral_value.try_round_u64().is_ok());
-
-            let borrowed_liquidity_wads = collateral_value
+            let collateral_value = Decimal::from(obligation_collateral).try_mul(collateral_token_price)?;
+            let borrowed_value = collateral_value
                 .try_mul(obligation_ltv)?
-                .try_add(Decimal::from_scaled_val(1u128))? // ensure loan is unhealthy
-                .max(Decimal::from(2u64)); // avoid dust account closure
-
-            // Ensure that borrow value fits in u64
-            prop_assume!(borrowed_liquidity_wads.try_round_u64().is_ok());
+                .try_add(Decimal::from_scaled_val(1u128))?; // ensure loan is unhealthy
+            let borrowed_liquidity_wads = Decimal::from(borrowed_liquidity);
 
             let obligation = Obligation {
-                deposited_collateral_tokens: obligation_collateral as u64,
+                deposited_collateral_tokens: obligation_collateral,
                 borrowed_liquidity_wads,
+                collateral_value,
+                borrowed_value,
                 ..Obligation::default()
             };
 
-            // Ensure that the token conversion fits in a Decimal
+            // Ensure that the close factor allows repaying at least one token
             {
-                let token_converter = MockConverter(token_conversion_rate);
-                let decimal_repay_amount = Decimal::from(obligation.max_liquidation_amount()?);
-                // Calculate the amount of collateral that will be received
-                let receive_liquidity_amount_result =
-                    token_converter.convert(decimal_repay_amount, &Pubkey::default());
-                prop_assume!(receive_liquidity_amount_result.is_ok());
+                let close_factor = collateral_reserve_config.liquidation_close_factor(obligation.loan_to_value()?)?;
+                prop_assume!(obligation.max_liquidation_amount(close_factor)? > 0);
             }
 
             // Liquidate with max amount to ensure obligation can be liquidated
             let liquidate_result = Reserve::_liquidate_obligation(
                 &obligation,
                 u64::MAX,
-                &Pubkey::default(),
-                collateral_exchange_rate,
                 collateral_reserve_config,
-                MockConverter(token_conversion_rate)
             );
 
             let liquidate_result = liquidate_result.unwrap();
-            let expected_withdraw_amount = liquidity_in_other_collateral(
-                liquidate_result.repay_amount,
-                collateral_exchange_rate,
-                token_conversion_rate,
-            )?.min(obligation.deposited_collateral_tokens.into());
+            let expected_withdraw_amount = Decimal::from(liquidate_result.repay_amount)
+                .try_div(borrowed_liquidity_wads)?
+                .try_mul(obligation.loan_to_value()?)?
+                .try_mul(obligation_collateral)?
+                .min(obligation.deposited_collateral_tokens.into());
 
             assert!(liquidate_result.repay_amount > 0);
             assert!(liquidate_result.withdraw_amount > 0);
@@ -887,7 +1026,7 @@ mod test {
             let min_withdraw_amount = expected_withdraw_amount.try_floor_u64()?;
             let max_withdraw_amount = expected_withdraw_amount.try_ceil_u64()?;
             let max_repay_amount = obligation.borrowed_liquidity_wads
-                .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
+                .try_mul(max_close_factor)?
                 .try_ceil_u64()?;
 
             assert!(liquidate_result.withdraw_amount >= min_withdraw_amount);
@@ -897,19 +1036,53 @@ mod test {
             let defaulted = liquidate_result.withdraw_amount == obligation.deposited_collateral_tokens;
             if defaulted {
                 assert_eq!(liquidate_result.settle_amount, borrowed_liquidity_wads);
-                assert!(liquidate_result.repay_amount < liquidate_result.settle_amount.try_floor_u64()?);
+                assert!(liquidate_result.repay_amount <= liquidate_result.settle_amount.try_floor_u64()?);
             } else {
                 assert_eq!(liquidate_result.settle_amount.try_ceil_u64()?, liquidate_result.repay_amount);
                 assert!(liquidate_result.withdraw_amount < obligation.deposited_collateral_tokens);
             }
         }
 
+        #[test]
+        fn liquidation_close_factor(
+            (ltv, liquidation_threshold) in unhealthy_rates(),
+            (min_liquidation_close_factor, max_liquidation_close_factor) in close_factors(),
+            ltv_increase in 0..=100u64,
+        ) {
+            let config = ReserveConfig {
+                liquidation_threshold,
+                min_liquidation_close_factor,
+                max_liquidation_close_factor,
+                ..ReserveConfig::default()
+            };
+            let min_close_factor = Rate::from_percent(min_liquidation_close_factor);
+            let max_close_factor = Rate::from_percent(max_liquidation_close_factor);
+
+            let close_factor = config.liquidation_close_factor(ltv)?;
+            assert!(close_factor >= min_close_factor);
+            assert!(close_factor <= max_close_factor);
+
+            // Close factor never decreases as the obligation becomes less healthy
+            let worse_ltv = ltv.try_add(Decimal::from_percent(ltv_increase as u8))?;
+            assert!(config.liquidation_close_factor(worse_ltv)? >= close_factor);
+
+            if ltv >= Decimal::one() {
+                assert_eq!(close_factor, max_close_factor);
+            }
+            assert_eq!(
+                config.liquidation_close_factor(Decimal::from_percent(liquidation_threshold))?,
+                min_close_factor
+            );
+        }
+
         #[test]
         fn current_borrow_rate(
             total_liquidity in 0..=MAX_LIQUIDITY,
             borrowed_percent in 0..=WAD,
             optimal_utilization_rate in 0..=100u8,
-            (min_borrow_rate, optimal_borrow_rate, max_borrow_rate) in borrow_rates(),
+            base_borrow_rate in 0..=u8::MAX,
+            borrow_rate_slope_1 in 0..=u8::MAX,
+            borrow_rate_slope_2 in 0..=u8::MAX,
         ) {
             let borrowed_amount_wads = Decimal::from(total_liquidity).try_mul(Rate::from_scaled_val(borrowed_percent))?;
             let reserve = Reserve {
@@ -918,19 +1091,22 @@ mod test {
                     available_amount: total_liquidity - borrowed_amount_wads.try_round_u64()?,
                     ..ReserveLiquidity::default()
                 },
-                config: ReserveConfig { optimal_utilization_rate, min_borrow_rate, optimal_borrow_rate, max_borrow_rate, ..ReserveConfig::default() },
+                config: ReserveConfig { optimal_utilization_rate, base_borrow_rate, borrow_rate_slope_1, borrow_rate_slope_2, ..ReserveConfig::default() },
                 ..Reserve::default()
             };
 
+            let min_borrow_rate = Rate::from_percent(base_borrow_rate);
+            let optimal_borrow_rate = min_borrow_rate.try_add(Rate::from_percent(borrow_rate_slope_1))?;
+            let max_borrow_rate = optimal_borrow_rate.try_add(Rate::from_percent(borrow_rate_slope_2))?;
+
             let current_borrow_rate = reserve.current_borrow_rate()?;
-            assert!(current_borrow_rate >= Rate::from_percent(min_borrow_rate));
-            assert!(current_borrow_rate <= Rate::from_percent(max_borrow_rate));
+            assert!(current_borrow_rate >= min_borrow_rate);
+            assert!(current_borrow_rate <= max_borrow_rate);
 
-            let optimal_borrow_rate = Rate::from_percent(optimal_borrow_rate);
             let current_rate = reserve.liquidity.utilization_rate()?;
             match current_rate.cmp(&Rate::from_percent(optimal_utilization_rate)) {
                 Ordering::Less => {
-                    if min_borrow_rate == reserve.config.optimal_borrow_rate {
+                    if borrow_rate_slope_1 == 0 {
                         assert_eq!(current_borrow_rate, optimal_borrow_rate);
                     } else {
                         assert!(current_borrow_rate < optimal_borrow_rate);
@@ -938,7 +1114,7 @@ mod test {
                 }
                 Ordering::Equal => assert!(current_borrow_rate == optimal_borrow_rate),
                 Ordering::Greater => {
-                    if max_borrow_rate == reserve.config.optimal_borrow_rate {
+                    if borrow_rate_slope_2 == 0 {
                         assert_eq!(current_borrow_rate, optimal_borrow_rate);
                     } else {
                         assert!(current_borrow_rate > optimal_borrow_rate);
@@ -1098,8 +1274,7 @@ mod test {
                     ..ReserveLiquidity::default()
                 },
                 config: ReserveConfig {
-                    min_borrow_rate: borrow_rate,
-                    optimal_borrow_rate: borrow_rate,
+                    base_borrow_rate: borrow_rate,
                     optimal_utilization_rate: 100,
                     ..ReserveConfig::default()
                 },
@@ -1149,7 +1324,7 @@ mod test {
                     ..ReserveLiquidity::default()
                 },
                 config: ReserveConfig {
-                    max_borrow_rate: borrow_rate,
+                    borrow_rate_slope_2: borrow_rate,
                     ..ReserveConfig::default()
                 },
                 ..Reserve::default()
@@ -1164,6 +1339,109 @@ mod test {
             }
         }
 
+        #[test]
+        fn protocol_fees(
+            slots_elapsed in 0..=SLOTS_PER_YEAR,
+            borrowed_liquidity in 0..=u32::MAX as u64,
+            available_liquidity in 0..=u32::MAX as u64,
+            borrow_rate in 0..=u8::MAX,
+            protocol_take_rate in 0..=100u8,
+        ) {
+            let borrowed_amount_wads = Decimal::from(borrowed_liquidity);
+            let mut reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    borrowed_amount_wads,
+                    available_amount: available_liquidity,
+                    ..ReserveLiquidity::default()
+                },
+                config: ReserveConfig {
+                    borrow_rate_slope_2: borrow_rate,
+                    fees: ReserveFees {
+                        protocol_take_rate,
+                        ..ReserveFees::default()
+                    },
+                    ..ReserveConfig::default()
+                },
+                ..Reserve::default()
+            };
+
+            reserve.accrue_interest(slots_elapsed)?;
+
+            // the protocol only takes a share of accrued interest
+            let interest = reserve.liquidity.borrowed_amount_wads.try_sub(borrowed_amount_wads)?;
+            let protocol_fees = reserve.liquidity.accumulated_protocol_fees_wads;
+            assert!(protocol_fees <= interest);
+            assert!(reserve.liquidity.total_supply()? >= Decimal::from(available_liquidity + borrowed_liquidity));
+
+            // fees can only be redeemed from available liquidity
+            let redeem_amount = reserve.redeem_fees()?;
+            assert!(redeem_amount <= available_liquidity);
+            assert!(Decimal::from(redeem_amount) <= protocol_fees);
+            assert_eq!(reserve.liquidity.available_amount, available_liquidity - redeem_amount);
+            if redeem_amount < available_liquidity {
+                assert!(reserve.liquidity.accumulated_protocol_fees_wads < Decimal::one());
+            }
+        }
+
+        #[test]
+        fn deposit_limit(
+            (deposit_limit, total_liquidity) in limit_boundary_amounts(),
+            existing_pct in 0..=100u64,
+        ) {
+            let available_liquidity = total_liquidity / 100 * existing_pct;
+            let liquidity_amount = total_liquidity - available_liquidity;
+            let mut reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    available_amount: available_liquidity,
+                    ..ReserveLiquidity::default()
+                },
+                config: ReserveConfig {
+                    deposit_limit,
+                    ..ReserveConfig::default()
+                },
+                ..Reserve::default()
+            };
+
+            let result = reserve.deposit_liquidity(liquidity_amount);
+            if deposit_limit > 0 && total_liquidity > deposit_limit {
+                assert_eq!(result, Err(LendingError::ReserveDepositLimitExceeded.into()));
+                assert_eq!(reserve.liquidity.available_amount, available_liquidity);
+            } else {
+                assert!(result.is_ok());
+                assert_eq!(reserve.liquidity.available_amount, total_liquidity);
+            }
+        }
+
+        #[test]
+        fn borrow_limit(
+            (borrow_limit, total_borrows) in limit_boundary_amounts(),
+            existing_pct in 0..=100u64,
+        ) {
+            let borrowed_liquidity = total_borrows / 100 * existing_pct;
+            let borrow_amount = total_borrows - borrowed_liquidity;
+            let mut reserve = Reserve {
+                liquidity: ReserveLiquidity {
+                    available_amount: MAX_LIQUIDITY,
+                    borrowed_amount_wads: Decimal::from(borrowed_liquidity),
+                    ..ReserveLiquidity::default()
+                },
+                config: ReserveConfig {
+                    borrow_limit,
+                    ..ReserveConfig::default()
+                },
+                ..Reserve::default()
+            };
+
+            let result = reserve.borrow_liquidity(borrow_amount);
+            if borrow_limit > 0 && total_borrows > borrow_limit {
+                assert_eq!(result, Err(LendingError::ReserveBorrowLimitExceeded.into()));
+                assert_eq!(reserve.liquidity.borrowed_amount_wads, Decimal::from(borrowed_liquidity));
+            } else {
+                assert_eq!(result, Ok(()));
+                assert_eq!(reserve.liquidity.borrowed_amount_wads, Decimal::from(total_borrows));
+            }
+        }
+
         #[test]
         fn borrow_fee_calculation(
             borrow_fee_wad in 0..WAD, // at WAD, fee == borrow amount, which fails
@@ -1174,6 +1452,7 @@ mod test {
             let fees = ReserveFees {
                 borrow_fee_wad,
                 host_fee_percentage,
+                ..ReserveFees::default()
             };
             let (total_fee, host_fee) = fees.calculate_borrow_fees(borrow_amount)?;
 
@@ -1207,27 +1486,26 @@ mod test {
 
     #[test]
     fn liquidate_amount_too_small() {
-        let conversion_rate = Decimal::from_scaled_val(PERCENT_SCALER as u128); // 1%
-        let collateral_exchange_rate = CollateralExchangeRate(Rate::one());
         let collateral_reserve_config = &ReserveConfig {
             liquidation_threshold: 80u8,
             liquidation_bonus: 5u8,
+            min_liquidation_close_factor: 50u8,
+            max_liquidation_close_factor: 100u8,
             ..ReserveConfig::default()
         };
 
         let obligation = Obligation {
             deposited_collateral_tokens: 1,
             borrowed_liquidity_wads: Decimal::from(100u64),
+            collateral_value: Decimal::from(100u64),
+            borrowed_value: Decimal::from(100u64),
             ..Obligation::default()
         };
 
         let liquidate_result = Reserve::_liquidate_obligation(
             &obligation,
             1u64, // converts to 0.01 collateral
-            &Pubkey::default(),
-            collateral_exchange_rate,
             collateral_reserve_config,
-            MockConverter(conversion_rate),
         );
 
         assert_eq!(
@@ -1238,8 +1516,6 @@ mod test {
 
     #[test]
     fn liquidate_dust_obligation() {
-        let conversion_rate = Decimal::one();
-        let collateral_exchange_rate = CollateralExchangeRate(Rate::one());
         let collateral_reserve_config = &ReserveConfig {
             liquidation_threshold: 80u8,
             liquidation_bonus: 5u8,
@@ -1251,18 +1527,15 @@ mod test {
             borrowed_liquidity_wads: Decimal::one()
                 .try_add(Decimal::from_scaled_val(1u128))
                 .unwrap(),
+            collateral_value: Decimal::one(),
+            borrowed_value: Decimal::one()
+                .try_add(Decimal::from_scaled_val(1u128))
+                .unwrap(),
             ..Obligation::default()
         };
 
-        let liquidate_result = Reserve::_liquidate_obligation(
-            &obligation,
-            2,
-            &Pubkey::default(),
-            collateral_exchange_rate,
-            collateral_reserve_config,
-            MockConverter(conversion_rate),
-        )
-        .unwrap();
+        let liquidate_result =
+            Reserve::_liquidate_obligation(&obligation, 2, collateral_reserve_config).unwrap();
 
         assert_eq!(
             liquidate_result.repay_amount,
@@ -1278,11 +1551,81 @@ mod test {
         );
     }
 
+    #[test]
+    fn unconfigured_liquidation_close_factor() {
+        let config = ReserveConfig {
+            liquidation_threshold: 80,
+            ..ReserveConfig::default()
+        };
+
+        for ltv in &[80u8, 90, 100, 150] {
+            assert_eq!(
+                config.liquidation_close_factor(Decimal::from_percent(*ltv)),
+                Ok(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))
+            );
+        }
+    }
+
+    #[test]
+    fn unpack_stored_borrow_rates() {
+        // min, optimal and max borrow rates as packed by earlier program versions
+        const BORROW_RATES_OFFSET: usize = 242;
+        let mut data = [0u8; Reserve::LEN];
+        Reserve::default().pack_into_slice(&mut data);
+        data[BORROW_RATES_OFFSET..BORROW_RATES_OFFSET + 3].copy_from_slice(&[2, 4, 30]);
+
+        let reserve = Reserve::unpack_from_slice(&data).unwrap();
+        assert_eq!(reserve.config.base_borrow_rate, 2);
+        assert_eq!(reserve.config.borrow_rate_slope_1, 2);
+        assert_eq!(reserve.config.borrow_rate_slope_2, 26);
+        assert_eq!(
+            reserve.config.rate_model().borrow_rate(Rate::one()),
+            Ok(Rate::from_percent(30))
+        );
+
+        let mut packed = [0u8; Reserve::LEN];
+        reserve.pack_into_slice(&mut packed);
+        assert_eq!(packed[..], data[..]);
+    }
+
+    #[test]
+    fn no_deposit_limit() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: MAX_LIQUIDITY,
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+
+        assert!(reserve.deposit_liquidity(MAX_LIQUIDITY).is_ok());
+        assert_eq!(reserve.liquidity.available_amount, 2 * MAX_LIQUIDITY);
+    }
+
+    #[test]
+    fn no_borrow_limit() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: MAX_LIQUIDITY,
+                borrowed_amount_wads: Decimal::from(MAX_LIQUIDITY),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+
+        assert_eq!(reserve.borrow_liquidity(MAX_LIQUIDITY), Ok(()));
+        assert_eq!(
+            reserve.liquidity.borrowed_amount_wads,
+            Decimal::from(2 * MAX_LIQUIDITY)
+        );
+    }
+
     #[test]
     fn borrow_fee_calculation_min_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         };
 
         // only 2 tokens borrowed, get error
@@ -1304,6 +1647,7 @@ mod test {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             host_fee_percentage: 0,
+            protocol_take_rate: 0,
         };
 
         // only 2 tokens borrowed, ok
@@ -1326,6 +1670,7 @@ mod test {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         };
 
         let (total_fee, host_fee) = fees.calculate_borrow_fees(1000).unwrap();
@@ -1339,6 +1684,7 @@ mod test {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             host_fee_percentage: 0,
+            protocol_take_rate: 0,
         };
 
         let (total_fee, host_fee) = fees.calculate_borrow_fees(1000).unwrap();
@@ -42,8 +42,8 @@ async fn test_success() {
 
     // Configure reserve to a fixed borrow rate of 1%
     const BORROW_RATE: u8 = 1;
-    reserve_config.min_borrow_rate = BORROW_RATE;
-    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.base_borrow_rate = BORROW_RATE;
+    reserve_config.borrow_rate_slope_1 = 0;
     reserve_config.optimal_utilization_rate = 100;
 
     let usdc_reserve = add_reserve(
@@ -0,0 +1,408 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::close_obligation,
+    math::{Decimal, TrySub},
+    processor::process_instruction,
+};
+
+const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    // a remaining borrow below the closeable amount is written off as dust
+    let dust_amount = Decimal::from_scaled_val(500_000_000_000_000_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: 1,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &sol_reserve,
+            collateral_reserve: &usdc_reserve,
+            collateral_amount: 0,
+            borrowed_liquidity_wads: dust_amount,
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_borrowed_amount_wads = sol_reserve
+        .get_state(&mut banks_client)
+        .await
+        .liquidity
+        .borrowed_amount_wads;
+    let obligation_lamports = banks_client
+        .get_account(obligation.pubkey)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+
+    let destination = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(
+        &[close_obligation(
+            spl_token_lending::id(),
+            obligation.pubkey,
+            sol_reserve.pubkey,
+            obligation.token_mint,
+            obligation.token_account,
+            user_accounts_owner.pubkey(),
+            destination,
+            lending_market.pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert!(banks_client
+        .get_account(obligation.pubkey)
+        .await
+        .unwrap()
+        .is_none());
+    let destination_account = banks_client
+        .get_account(destination)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination_account.lamports, obligation_lamports);
+
+    let sol_reserve = sol_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        sol_reserve.liquidity.borrowed_amount_wads,
+        initial_borrowed_amount_wads.try_sub(dust_amount).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_obligation_not_empty() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: LAMPORTS_TO_SOL,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &sol_reserve,
+            collateral_reserve: &usdc_reserve,
+            collateral_amount: 0,
+            borrowed_liquidity_wads: Decimal::from(LAMPORTS_TO_SOL),
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_obligation(
+            spl_token_lending::id(),
+            obligation.pubkey,
+            sol_reserve.pubkey,
+            obligation.token_mint,
+            obligation.token_account,
+            user_accounts_owner.pubkey(),
+            Pubkey::new_unique(),
+            lending_market.pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ObligationNotEmpty as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // fully repaid obligation, so the obligation token supply is zero
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &sol_reserve,
+            collateral_reserve: &usdc_reserve,
+            collateral_amount: 0,
+            borrowed_liquidity_wads: Decimal::zero(),
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // an empty token account holds the entire zero supply
+    let invalid_owner = Keypair::new();
+    let invalid_token_account = create_token_account(
+        &mut banks_client,
+        obligation.token_mint,
+        &payer,
+        Some(invalid_owner.pubkey()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_obligation(
+            spl_token_lending::id(),
+            obligation.pubkey,
+            sol_reserve.pubkey,
+            obligation.token_mint,
+            invalid_token_account,
+            invalid_owner.pubkey(),
+            invalid_owner.pubkey(),
+            lending_market.pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_success_without_recorded_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(20_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // obligation created before owners were recorded
+    let obligation = add_obligation_with_owner(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &sol_reserve,
+            collateral_reserve: &usdc_reserve,
+            collateral_amount: 0,
+            borrowed_liquidity_wads: Decimal::zero(),
+        },
+        Pubkey::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let obligation_lamports = banks_client
+        .get_account(obligation.pubkey)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+
+    let destination = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(
+        &[close_obligation(
+            spl_token_lending::id(),
+            obligation.pubkey,
+            sol_reserve.pubkey,
+            obligation.token_mint,
+            obligation.token_account,
+            user_accounts_owner.pubkey(),
+            destination,
+            lending_market.pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    assert!(banks_client
+        .get_account(obligation.pubkey)
+        .await
+        .unwrap()
+        .is_none());
+    let destination_account = banks_client
+        .get_account(destination)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination_account.lamports, obligation_lamports);
+}
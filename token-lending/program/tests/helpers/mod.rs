@@ -16,9 +16,10 @@ use spl_token::{
 use spl_token_lending::{
     instruction::{
         borrow_reserve_liquidity, deposit_reserve_liquidity, init_lending_market, init_obligation,
-        init_reserve, liquidate_obligation, BorrowAmountType,
+        init_reserve, liquidate_obligation, refresh_obligation, BorrowAmountType, PriceAccounts,
     },
     math::{Decimal, Rate, TryAdd, TryMul},
+    oracle::pyth,
     processor::process_instruction,
     state::{
         LendingMarket, NewReserveParams, Obligation, Reserve, ReserveCollateral, ReserveConfig,
@@ -34,13 +35,18 @@ pub const TEST_RESERVE_CONFIG: ReserveConfig = ReserveConfig {
     loan_to_value_ratio: 50,
     liquidation_bonus: 5,
     liquidation_threshold: 55,
-    min_borrow_rate: 0,
-    optimal_borrow_rate: 4,
-    max_borrow_rate: 30,
+    min_liquidation_close_factor: 50,
+    max_liquidation_close_factor: 50,
+    base_borrow_rate: 0,
+    borrow_rate_slope_1: 4,
+    borrow_rate_slope_2: 26,
+    deposit_limit: u64::MAX,
+    borrow_limit: u64::MAX,
     fees: ReserveFees {
         borrow_fee_wad: 100_000_000_000,
         /// 0.00001% (Aave borrow fee)
         host_fee_percentage: 20,
+        protocol_take_rate: 10,
     },
 };
 
@@ -156,6 +162,24 @@ pub fn add_obligation(
     user_accounts_owner: &Keypair,
     lending_market: &TestLendingMarket,
     args: AddObligationArgs,
+) -> TestObligation {
+    add_obligation_with_owner(
+        test,
+        user_accounts_owner,
+        lending_market,
+        args,
+        user_accounts_owner.pubkey(),
+    )
+}
+
+/// Add an obligation with the given recorded owner, which is the default pubkey for
+/// obligations created before owners were recorded
+pub fn add_obligation_with_owner(
+    test: &mut ProgramTest,
+    user_accounts_owner: &Keypair,
+    lending_market: &TestLendingMarket,
+    args: AddObligationArgs,
+    owner: Pubkey,
 ) -> TestObligation {
     let AddObligationArgs {
         borrow_reserve,
@@ -199,12 +223,15 @@ pub fn add_obligation(
         u32::MAX as u64,
         &Obligation {
             version: PROGRAM_VERSION,
+            last_update_slot: 0,
             deposited_collateral_tokens: collateral_amount,
             collateral_reserve: collateral_reserve.pubkey,
             cumulative_borrow_rate_wads: Decimal::one(),
             borrowed_liquidity_wads,
             borrow_reserve: borrow_reserve.pubkey,
             token_mint: token_mint_pubkey,
+            owner,
+            ..Obligation::default()
         },
         &spl_token_lending::id(),
     );
@@ -215,6 +242,7 @@ pub fn add_obligation(
         token_account: token_account_pubkey,
         borrow_reserve: borrow_reserve.pubkey,
         collateral_reserve: collateral_reserve.pubkey,
+        owner,
     }
 }
 
@@ -231,7 +259,9 @@ pub struct AddReserveArgs {
     pub initial_borrow_rate: u8,
     pub collateral_amount: u64,
     pub fees_amount: u64,
+    pub protocol_fees_amount: u64,
     pub dex_market_pubkey: Option<Pubkey>,
+    pub oracle_pubkey: Option<Pubkey>,
 }
 
 pub fn add_reserve(
@@ -252,7 +282,9 @@ pub fn add_reserve(
         initial_borrow_rate,
         collateral_amount,
         fees_amount,
+        protocol_fees_amount,
         dex_market_pubkey,
+        oracle_pubkey,
     } = args;
 
     let is_native = if liquidity_mint_pubkey == spl_token::native_mint::id() {
@@ -338,12 +370,27 @@ pub fn add_reserve(
         &spl_token::id(),
     );
 
+    let liquidity_fees_receiver_pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        liquidity_fees_receiver_pubkey,
+        u32::MAX as u64,
+        &Token {
+            mint: liquidity_mint_pubkey,
+            owner: lending_market.authority,
+            amount: 0,
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+
     let reserve_keypair = Keypair::new();
     let reserve_pubkey = reserve_keypair.pubkey();
     let reserve_liquidity = ReserveLiquidity::new(
         liquidity_mint_pubkey,
         liquidity_mint_decimals,
         liquidity_supply_pubkey,
+        liquidity_fees_receiver_pubkey,
     );
     let reserve_collateral = ReserveCollateral::new(
         collateral_mint_pubkey,
@@ -355,12 +402,14 @@ pub fn add_reserve(
         current_slot: 1u64.wrapping_sub(slots_elapsed),
         lending_market: lending_market.pubkey,
         dex_market: dex_market_pubkey.into(),
+        oracle: oracle_pubkey.into(),
         liquidity: reserve_liquidity,
         collateral: reserve_collateral,
         config,
     });
     reserve.deposit_liquidity(liquidity_amount).unwrap();
     reserve.liquidity.borrow(borrow_amount).unwrap();
+    reserve.liquidity.accumulated_protocol_fees_wads = Decimal::from(protocol_fees_amount);
     let borrow_rate_multiplier = Rate::one()
         .try_add(Rate::from_percent(initial_borrow_rate))
         .unwrap();
@@ -418,9 +467,53 @@ pub fn add_reserve(
         collateral_supply: collateral_supply_pubkey,
         collateral_fees_receiver: collateral_fees_receiver_pubkey,
         collateral_host: collateral_host_pubkey,
+        liquidity_fees_receiver: liquidity_fees_receiver_pubkey,
         user_liquidity_account: user_liquidity_pubkey,
         user_collateral_account: user_collateral_pubkey,
         dex_market: dex_market_pubkey,
+        oracle: oracle_pubkey,
+    }
+}
+
+/// Add a trading Pyth price account which was published at the genesis slot
+pub fn add_pyth_oracle(test: &mut ProgramTest, price: i64, exponent: i32) -> Pubkey {
+    let mut data = vec![0u8; 240];
+    data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    data[4..8].copy_from_slice(&2u32.to_le_bytes());
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&exponent.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[224..228].copy_from_slice(&1u32.to_le_bytes());
+
+    let oracle_pubkey = Pubkey::new_unique();
+    test.add_account(
+        oracle_pubkey,
+        Account {
+            lamports: u32::MAX as u64,
+            data,
+            owner: pyth::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    oracle_pubkey
+}
+
+/// Reserves which both have an oracle are priced by their oracles, otherwise by the dex market
+pub fn price_accounts(
+    collateral_reserve: &TestReserve,
+    borrow_reserve: &TestReserve,
+    dex_market: &TestDexMarket,
+    memory: Pubkey,
+) -> PriceAccounts {
+    match (collateral_reserve.oracle, borrow_reserve.oracle) {
+        (Some(collateral_reserve_oracle_pubkey), Some(borrow_reserve_oracle_pubkey)) => {
+            PriceAccounts::Oracles {
+                collateral_reserve_oracle_pubkey,
+                borrow_reserve_oracle_pubkey,
+            }
+        }
+        _ => dex_market.price_accounts(collateral_reserve, memory),
     }
 }
 
@@ -554,12 +647,6 @@ impl TestLendingMarket {
             user_accounts_owner,
         } = args;
 
-        let dex_market_orders_pubkey = if repay_reserve.dex_market.is_none() {
-            dex_market.asks_pubkey
-        } else {
-            dex_market.bids_pubkey
-        };
-
         let memory_keypair = Keypair::new();
         let user_transfer_authority = Keypair::new();
         let mut transaction = Transaction::new_with_payer(
@@ -580,6 +667,19 @@ impl TestLendingMarket {
                     amount,
                 )
                 .unwrap(),
+                refresh_obligation(
+                    spl_token_lending::id(),
+                    obligation.pubkey,
+                    withdraw_reserve.pubkey,
+                    repay_reserve.pubkey,
+                    self.pubkey,
+                    price_accounts(
+                        withdraw_reserve,
+                        repay_reserve,
+                        dex_market,
+                        memory_keypair.pubkey(),
+                    ),
+                ),
                 liquidate_obligation(
                     spl_token_lending::id(),
                     amount,
@@ -593,9 +693,6 @@ impl TestLendingMarket {
                     self.pubkey,
                     self.authority,
                     user_transfer_authority.pubkey(),
-                    dex_market.pubkey,
-                    dex_market_orders_pubkey,
-                    memory_keypair.pubkey(),
                 ),
             ],
             Some(&payer.pubkey()),
@@ -633,11 +730,12 @@ impl TestLendingMarket {
             obligation,
         } = args;
 
-        let dex_market_orders_pubkey = if deposit_reserve.dex_market.is_none() {
-            dex_market.asks_pubkey
-        } else {
-            dex_market.bids_pubkey
-        };
+        let price_accounts = price_accounts(
+            deposit_reserve,
+            borrow_reserve,
+            dex_market,
+            memory_keypair.pubkey(),
+        );
 
         let approve_amount = if borrow_amount_type == BorrowAmountType::CollateralDepositAmount {
             amount
@@ -663,6 +761,14 @@ impl TestLendingMarket {
                     65548,
                     &spl_token_lending::id(),
                 ),
+                refresh_obligation(
+                    spl_token_lending::id(),
+                    obligation.pubkey,
+                    deposit_reserve.pubkey,
+                    borrow_reserve.pubkey,
+                    self.pubkey,
+                    price_accounts,
+                ),
                 borrow_reserve_liquidity(
                     spl_token_lending::id(),
                     amount,
@@ -680,9 +786,7 @@ impl TestLendingMarket {
                     obligation.pubkey,
                     obligation.token_mint,
                     obligation.token_account,
-                    dex_market.pubkey,
-                    dex_market_orders_pubkey,
-                    memory_keypair.pubkey(),
+                    price_accounts,
                     Some(deposit_reserve.collateral_host),
                 ),
             ],
@@ -737,9 +841,11 @@ pub struct TestReserve {
     pub collateral_supply: Pubkey,
     pub collateral_fees_receiver: Pubkey,
     pub collateral_host: Pubkey,
+    pub liquidity_fees_receiver: Pubkey,
     pub user_liquidity_account: Pubkey,
     pub user_collateral_account: Pubkey,
     pub dex_market: Option<Pubkey>,
+    pub oracle: Option<Pubkey>,
 }
 
 impl TestReserve {
@@ -763,6 +869,7 @@ impl TestReserve {
         let collateral_fees_receiver_keypair = Keypair::new();
         let collateral_host_keypair = Keypair::new();
         let liquidity_supply_keypair = Keypair::new();
+        let liquidity_fees_receiver_keypair = Keypair::new();
         let user_collateral_token_keypair = Keypair::new();
         let user_transfer_authority_keypair = Keypair::new();
 
@@ -826,6 +933,13 @@ impl TestReserve {
                     Token::LEN as u64,
                     &spl_token::id(),
                 ),
+                create_account(
+                    &payer.pubkey(),
+                    &liquidity_fees_receiver_keypair.pubkey(),
+                    rent.minimum_balance(Token::LEN),
+                    Token::LEN as u64,
+                    &spl_token::id(),
+                ),
                 create_account(
                     &payer.pubkey(),
                     &user_collateral_token_keypair.pubkey(),
@@ -852,10 +966,12 @@ impl TestReserve {
                     collateral_mint_keypair.pubkey(),
                     collateral_supply_keypair.pubkey(),
                     collateral_fees_receiver_keypair.pubkey(),
+                    liquidity_fees_receiver_keypair.pubkey(),
                     lending_market.pubkey,
                     lending_market.owner.pubkey(),
                     user_transfer_authority_keypair.pubkey(),
                     dex_market_pubkey,
+                    None,
                 ),
             ],
             Some(&payer.pubkey()),
@@ -873,6 +989,7 @@ impl TestReserve {
                 &collateral_fees_receiver_keypair,
                 &collateral_host_keypair,
                 &liquidity_supply_keypair,
+                &liquidity_fees_receiver_keypair,
                 &user_collateral_token_keypair,
                 &user_transfer_authority_keypair,
             ],
@@ -894,9 +1011,11 @@ impl TestReserve {
                 collateral_supply: collateral_supply_keypair.pubkey(),
                 collateral_fees_receiver: collateral_fees_receiver_keypair.pubkey(),
                 collateral_host: collateral_host_keypair.pubkey(),
+                liquidity_fees_receiver: liquidity_fees_receiver_keypair.pubkey(),
                 user_liquidity_account,
                 user_collateral_account: user_collateral_token_keypair.pubkey(),
                 dex_market: dex_market_pubkey,
+                oracle: None,
             })
             .map_err(|e| e.unwrap())
     }
@@ -938,6 +1057,13 @@ impl TestReserve {
         genesis_accounts
             .fetch_and_insert(banks_client, self.liquidity_supply)
             .await;
+        println!(
+            "{}_liquidity_fees_receiver: {}",
+            self.name, self.liquidity_fees_receiver
+        );
+        genesis_accounts
+            .fetch_and_insert(banks_client, self.liquidity_fees_receiver)
+            .await;
         println!(
             "{}_user_collateral: {}",
             self.name, self.user_collateral_account
@@ -970,6 +1096,10 @@ impl TestReserve {
         assert_eq!(self.lending_market, reserve.lending_market);
         assert_eq!(self.liquidity_mint, reserve.liquidity.mint_pubkey);
         assert_eq!(self.liquidity_supply, reserve.liquidity.supply_pubkey);
+        assert_eq!(
+            self.liquidity_fees_receiver,
+            reserve.liquidity.fees_receiver
+        );
         assert_eq!(self.collateral_mint, reserve.collateral.mint_pubkey);
         assert_eq!(self.collateral_supply, reserve.collateral.supply_pubkey);
         assert_eq!(self.config, reserve.config);
@@ -995,6 +1125,7 @@ pub struct TestObligation {
     pub token_account: Pubkey,
     pub collateral_reserve: Pubkey,
     pub borrow_reserve: Pubkey,
+    pub owner: Pubkey,
 }
 
 impl TestObligation {
@@ -1016,6 +1147,7 @@ impl TestObligation {
             token_account: obligation_token_account_keypair.pubkey(),
             collateral_reserve: deposit_reserve.pubkey,
             borrow_reserve: borrow_reserve.pubkey,
+            owner: user_accounts_owner.pubkey(),
         };
 
         let rent = banks_client.get_rent().await.unwrap();
@@ -1091,6 +1223,7 @@ impl TestObligation {
         assert!(obligation.cumulative_borrow_rate_wads >= Decimal::one());
         assert_eq!(obligation.borrow_reserve, self.borrow_reserve);
         assert_eq!(obligation.token_mint, self.token_mint);
+        assert_eq!(obligation.owner, self.owner);
     }
 }
 
@@ -1195,6 +1328,21 @@ impl TestDexMarket {
         }
     }
 
+    /// Loans are valued at the best ask and collateral at the best bid
+    pub fn price_accounts(&self, deposit_reserve: &TestReserve, memory: Pubkey) -> PriceAccounts {
+        let dex_market_order_book_side_pubkey = if deposit_reserve.dex_market.is_none() {
+            self.asks_pubkey
+        } else {
+            self.bids_pubkey
+        };
+
+        PriceAccounts::DexMarket {
+            dex_market_pubkey: self.pubkey,
+            dex_market_order_book_side_pubkey,
+            memory_pubkey: memory,
+        }
+    }
+
     pub async fn add_to_genesis(
         &self,
         banks_client: &mut BanksClient,
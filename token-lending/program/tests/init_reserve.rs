@@ -120,10 +120,12 @@ async fn test_already_initialized() {
             usdc_reserve.collateral_mint,
             usdc_reserve.collateral_supply,
             usdc_reserve.collateral_fees_receiver,
+            usdc_reserve.liquidity_fees_receiver,
             lending_market.pubkey,
             lending_market.owner.pubkey(),
             user_transfer_authority.pubkey(),
             Some(sol_usdc_dex_market.pubkey),
+            None,
         )],
         Some(&payer.pubkey()),
     );
@@ -176,6 +178,7 @@ async fn test_invalid_fees() {
         config.fees = ReserveFees {
             borrow_fee_wad: 1_000_000_000_000_000_001,
             host_fee_percentage: 0,
+            protocol_take_rate: 0,
         };
 
         assert_eq!(
@@ -194,7 +197,7 @@ async fn test_invalid_fees() {
             .await
             .unwrap_err(),
             TransactionError::InstructionError(
-                8,
+                9,
                 InstructionError::Custom(LendingError::InvalidConfig as u32)
             )
         );
@@ -206,6 +209,7 @@ async fn test_invalid_fees() {
         config.fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000,
             host_fee_percentage: 101,
+            protocol_take_rate: 0,
         };
 
         assert_eq!(
@@ -224,9 +228,176 @@ async fn test_invalid_fees() {
             .await
             .unwrap_err(),
             TransactionError::InstructionError(
-                8,
+                9,
                 InstructionError::Custom(LendingError::InvalidConfig as u32)
             )
         );
     }
+
+    // protocol take rate above 100%
+    {
+        let mut config = TEST_RESERVE_CONFIG;
+        config.fees = ReserveFees {
+            borrow_fee_wad: 10_000_000_000_000_000,
+            host_fee_percentage: 0,
+            protocol_take_rate: 101,
+        };
+
+        assert_eq!(
+            TestReserve::init(
+                "sol".to_owned(),
+                &mut banks_client,
+                &lending_market,
+                RESERVE_AMOUNT,
+                config,
+                spl_token::native_mint::id(),
+                sol_user_liquidity_account,
+                &payer,
+                &user_accounts_owner,
+                &sol_usdc_dex_market,
+            )
+            .await
+            .unwrap_err(),
+            TransactionError::InstructionError(
+                9,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_close_factors() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    // max liquidation close factor below min
+    {
+        let mut config = TEST_RESERVE_CONFIG;
+        config.min_liquidation_close_factor = 50;
+        config.max_liquidation_close_factor = 40;
+
+        assert_eq!(
+            TestReserve::init(
+                "sol".to_owned(),
+                &mut banks_client,
+                &lending_market,
+                RESERVE_AMOUNT,
+                config,
+                spl_token::native_mint::id(),
+                sol_user_liquidity_account,
+                &payer,
+                &user_accounts_owner,
+                &sol_usdc_dex_market,
+            )
+            .await
+            .unwrap_err(),
+            TransactionError::InstructionError(
+                9,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    // min liquidation close factor of zero
+    {
+        let mut config = TEST_RESERVE_CONFIG;
+        config.min_liquidation_close_factor = 0;
+
+        assert_eq!(
+            TestReserve::init(
+                "sol".to_owned(),
+                &mut banks_client,
+                &lending_market,
+                RESERVE_AMOUNT,
+                config,
+                spl_token::native_mint::id(),
+                sol_user_liquidity_account,
+                &payer,
+                &user_accounts_owner,
+                &sol_usdc_dex_market,
+            )
+            .await
+            .unwrap_err(),
+            TransactionError::InstructionError(
+                9,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_borrow_rates() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    // borrow rate at full utilization above 255%
+    let mut config = TEST_RESERVE_CONFIG;
+    config.base_borrow_rate = 100;
+    config.borrow_rate_slope_1 = 100;
+    config.borrow_rate_slope_2 = 56;
+
+    assert_eq!(
+        TestReserve::init(
+            "sol".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            RESERVE_AMOUNT,
+            config,
+            spl_token::native_mint::id(),
+            sol_user_liquidity_account,
+            &payer,
+            &user_accounts_owner,
+            &sol_usdc_dex_market,
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            9,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
 }
@@ -160,3 +160,205 @@ async fn test_success() {
             .unwrap()
     );
 }
+
+#[tokio::test]
+async fn test_oracle_price() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(200_000);
+
+    // set loan value to about 65% of collateral value at the dex price of ~$14 and
+    // about 90% at the oracle price of $10, so that it only gets liquidated by oracle price
+    const USDC_LOAN: u64 = 9 * FRACTIONAL_TO_USDC;
+    const USDC_LOAN_SOL_COLLATERAL: u64 = INITIAL_COLLATERAL_RATIO * LAMPORTS_TO_SOL;
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdc_oracle = add_pyth_oracle(&mut test, 1_000_000, -6);
+    let sol_oracle = add_pyth_oracle(&mut test, 10_000_000, -6);
+
+    // Loans are unhealthy if borrow is more than 80% of collateral
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.liquidation_threshold = 80;
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            config: reserve_config,
+            initial_borrow_rate: 1,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: USDC_LOAN * 101 / 100,
+            user_liquidity_amount: USDC_LOAN,
+            oracle_pubkey: Some(usdc_oracle),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            config: reserve_config,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            oracle_pubkey: Some(sol_oracle),
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &sol_reserve,
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+        },
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    lending_market
+        .liquidate(
+            &mut banks_client,
+            &payer,
+            LiquidateArgs {
+                repay_reserve: &usdc_reserve,
+                withdraw_reserve: &sol_reserve,
+                dex_market: &sol_usdc_dex_market,
+                amount: USDC_LOAN,
+                user_accounts_owner: &user_accounts_owner,
+                obligation: &usdc_obligation,
+            },
+        )
+        .await;
+
+    let usdc_liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply).await;
+    let usdc_loan_state = usdc_obligation.get_state(&mut banks_client).await;
+    let usdc_liquidated = usdc_liquidity_supply - INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL;
+    assert!(usdc_liquidated > USDC_LOAN / 2);
+    assert_eq!(
+        usdc_liquidated,
+        usdc_loan_state
+            .borrowed_liquidity_wads
+            .try_floor_u64()
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_dynamic_close_factor() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(200_000);
+
+    // set loan value to 90% of collateral value at the oracle price of $10, halfway between
+    // the liquidation threshold and 100%, so the close factor is halfway between min and max
+    const USDC_LOAN: u64 = 9 * FRACTIONAL_TO_USDC;
+    const USDC_LOAN_SOL_COLLATERAL: u64 = INITIAL_COLLATERAL_RATIO * LAMPORTS_TO_SOL;
+
+    let user_accounts_owner = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let usdc_oracle = add_pyth_oracle(&mut test, 1_000_000, -6);
+    let sol_oracle = add_pyth_oracle(&mut test, 10_000_000, -6);
+
+    // Loans are unhealthy if borrow is more than 80% of collateral, and up to 60% of the
+    // loan can be repaid at 90%
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.liquidation_threshold = 80;
+    reserve_config.min_liquidation_close_factor = 20;
+    reserve_config.max_liquidation_close_factor = 100;
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            config: reserve_config,
+            initial_borrow_rate: 1,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: USDC_LOAN * 101 / 100,
+            user_liquidity_amount: USDC_LOAN,
+            oracle_pubkey: Some(usdc_oracle),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            config: reserve_config,
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            oracle_pubkey: Some(sol_oracle),
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &sol_reserve,
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+        },
+    );
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    lending_market
+        .liquidate(
+            &mut banks_client,
+            &payer,
+            LiquidateArgs {
+                repay_reserve: &usdc_reserve,
+                withdraw_reserve: &sol_reserve,
+                dex_market: &sol_usdc_dex_market,
+                amount: USDC_LOAN,
+                user_accounts_owner: &user_accounts_owner,
+                obligation: &usdc_obligation,
+            },
+        )
+        .await;
+
+    let usdc_liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply).await;
+    let usdc_liquidated = usdc_liquidity_supply - INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL;
+    assert!(usdc_liquidated >= USDC_LOAN * 59 / 100);
+    assert!(usdc_liquidated <= USDC_LOAN * 61 / 100);
+}
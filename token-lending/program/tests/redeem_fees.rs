@@ -0,0 +1,384 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{redeem_fees, set_lending_market_owner},
+    math::Decimal,
+    processor::process_instruction,
+};
+
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    const PROTOCOL_FEES_AMOUNT: u64 = 2 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: 10 * FRACTIONAL_TO_USDC,
+            protocol_fees_amount: PROTOCOL_FEES_AMOUNT,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let destination_liquidity = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(lending_market.owner.pubkey()),
+        None,
+    )
+    .await;
+
+    let initial_available_amount = usdc_reserve
+        .get_state(&mut banks_client)
+        .await
+        .liquidity
+        .available_amount;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[redeem_fees(
+            spl_token_lending::id(),
+            usdc_reserve.pubkey,
+            usdc_reserve.liquidity_supply,
+            usdc_reserve.liquidity_fees_receiver,
+            destination_liquidity,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let reserve = usdc_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        reserve.liquidity.accumulated_protocol_fees_wads,
+        Decimal::zero()
+    );
+    assert_eq!(
+        reserve.liquidity.available_amount,
+        initial_available_amount - PROTOCOL_FEES_AMOUNT
+    );
+
+    let fees_balance =
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_fees_receiver).await;
+    assert_eq!(fees_balance, 0);
+
+    let destination_balance = get_token_balance(&mut banks_client, destination_liquidity).await;
+    assert_eq!(destination_balance, PROTOCOL_FEES_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            protocol_fees_amount: FRACTIONAL_TO_USDC,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let destination_liquidity = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(lending_market.owner.pubkey()),
+        None,
+    )
+    .await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[redeem_fees(
+            spl_token_lending::id(),
+            usdc_reserve.pubkey,
+            usdc_reserve.liquidity_supply,
+            usdc_reserve.liquidity_fees_receiver,
+            destination_liquidity,
+            lending_market.pubkey,
+            invalid_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_fees_receiver() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            protocol_fees_amount: FRACTIONAL_TO_USDC,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let destination_liquidity = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(lending_market.owner.pubkey()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[redeem_fees(
+            spl_token_lending::id(),
+            usdc_reserve.pubkey,
+            usdc_reserve.liquidity_supply,
+            Pubkey::new_unique(),
+            destination_liquidity,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_new_market_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    const PROTOCOL_FEES_AMOUNT: u64 = FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: 10 * FRACTIONAL_TO_USDC,
+            protocol_fees_amount: PROTOCOL_FEES_AMOUNT,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let new_owner = Keypair::new();
+    let destination_liquidity = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(new_owner.pubkey()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_owner(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                new_owner.pubkey(),
+            ),
+            redeem_fees(
+                spl_token_lending::id(),
+                usdc_reserve.pubkey,
+                usdc_reserve.liquidity_supply,
+                usdc_reserve.liquidity_fees_receiver,
+                destination_liquidity,
+                lending_market.pubkey,
+                new_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &lending_market.owner, &new_owner],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let destination_balance = get_token_balance(&mut banks_client, destination_liquidity).await;
+    assert_eq!(destination_balance, PROTOCOL_FEES_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_invalid_destination_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    let user_accounts_owner = Keypair::new();
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            protocol_fees_amount: FRACTIONAL_TO_USDC,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let destination_liquidity = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(user_accounts_owner.pubkey()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[redeem_fees(
+            spl_token_lending::id(),
+            usdc_reserve.pubkey,
+            usdc_reserve.liquidity_supply,
+            usdc_reserve.liquidity_fees_receiver,
+            destination_liquidity,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -0,0 +1,339 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token_lending::{
+    error::LendingError,
+    instruction::{accrue_reserve_interest, refresh_obligation},
+    math::Decimal,
+    processor::process_instruction,
+    state::SLOTS_PER_YEAR,
+};
+
+const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
+const FRACTIONAL_TO_USDC: u64 = 1_000_000;
+
+const INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL;
+const INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+
+const USDC_LOAN: u64 = 2 * FRACTIONAL_TO_USDC;
+const USDC_LOAN_SOL_COLLATERAL: u64 = 10 * LAMPORTS_TO_SOL;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(120_000);
+
+    let user_accounts_owner = Keypair::new();
+    let memory_keypair = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 80;
+
+    // Configure reserve to a fixed borrow rate of 1%
+    const BORROW_RATE: u8 = 1;
+    reserve_config.base_borrow_rate = BORROW_RATE;
+    reserve_config.borrow_rate_slope_1 = 0;
+    reserve_config.optimal_utilization_rate = 100;
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: USDC_LOAN,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            slots_elapsed: SLOTS_PER_YEAR,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            slots_elapsed: SLOTS_PER_YEAR,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &sol_reserve,
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &memory_keypair.pubkey(),
+                0,
+                65548,
+                &spl_token_lending::id(),
+            ),
+            accrue_reserve_interest(
+                spl_token_lending::id(),
+                vec![usdc_reserve.pubkey, sol_reserve.pubkey],
+            ),
+            refresh_obligation(
+                spl_token_lending::id(),
+                obligation.pubkey,
+                sol_reserve.pubkey,
+                usdc_reserve.pubkey,
+                lending_market.pubkey,
+                sol_usdc_dex_market.price_accounts(&sol_reserve, memory_keypair.pubkey()),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &memory_keypair], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_reserve.get_state(&mut banks_client).await;
+    let obligation = obligation.get_state(&mut banks_client).await;
+
+    assert_eq!(
+        obligation.cumulative_borrow_rate_wads,
+        usdc_reserve.cumulative_borrow_rate_wads
+    );
+    assert_eq!(obligation.last_update_slot, usdc_reserve.last_update_slot);
+    assert!(obligation.borrowed_liquidity_wads > Decimal::from(USDC_LOAN));
+
+    // loan is valued in the quote currency and collateral at the best SOL bid
+    assert_eq!(
+        obligation.borrowed_value,
+        obligation.borrowed_liquidity_wads
+    );
+    assert!(obligation.collateral_value > obligation.borrowed_value);
+    assert!(!obligation.stale);
+}
+
+#[tokio::test]
+async fn test_reserve_stale() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(120_000);
+
+    let user_accounts_owner = Keypair::new();
+    let memory_keypair = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: USDC_LOAN,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            slots_elapsed: SLOTS_PER_YEAR,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &sol_reserve,
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // borrow reserve interest was not accrued in the current slot
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &memory_keypair.pubkey(),
+                0,
+                65548,
+                &spl_token_lending::id(),
+            ),
+            refresh_obligation(
+                spl_token_lending::id(),
+                obligation.pubkey,
+                sol_reserve.pubkey,
+                usdc_reserve.pubkey,
+                lending_market.pubkey,
+                sol_usdc_dex_market.price_accounts(&sol_reserve, memory_keypair.pubkey()),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &memory_keypair], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ReserveStale as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_oracle_on_one_reserve() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(120_000);
+
+    let user_accounts_owner = Keypair::new();
+    let memory_keypair = Keypair::new();
+    let sol_usdc_dex_market = TestDexMarket::setup(&mut test, TestDexMarketPair::SOL_USDC);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let lending_market = add_lending_market(&mut test, usdc_mint.pubkey);
+    let sol_oracle = add_pyth_oracle(&mut test, 10_000_000, -6);
+
+    let usdc_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            borrow_amount: USDC_LOAN,
+            liquidity_amount: INITIAL_USDC_RESERVE_SUPPLY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let sol_reserve = add_reserve(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddReserveArgs {
+            liquidity_amount: INITIAL_SOL_RESERVE_SUPPLY_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            dex_market_pubkey: Some(sol_usdc_dex_market.pubkey),
+            oracle_pubkey: Some(sol_oracle),
+            config: TEST_RESERVE_CONFIG,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let obligation = add_obligation(
+        &mut test,
+        &user_accounts_owner,
+        &lending_market,
+        AddObligationArgs {
+            borrow_reserve: &usdc_reserve,
+            collateral_reserve: &sol_reserve,
+            collateral_amount: USDC_LOAN_SOL_COLLATERAL,
+            borrowed_liquidity_wads: Decimal::from(USDC_LOAN),
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    // only the collateral reserve has an oracle, which must not be ignored for dex market prices
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &memory_keypair.pubkey(),
+                0,
+                65548,
+                &spl_token_lending::id(),
+            ),
+            refresh_obligation(
+                spl_token_lending::id(),
+                obligation.pubkey,
+                sol_reserve.pubkey,
+                usdc_reserve.pubkey,
+                lending_market.pubkey,
+                sol_usdc_dex_market.price_accounts(&sol_reserve, memory_keypair.pubkey()),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &memory_keypair], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidOracleConfig as u32)
+        )
+    );
+}